@@ -0,0 +1,370 @@
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+use crate::db_config::{self, DbConfig};
+use crate::file_storage;
+use crate::safe_path;
+
+/// Name of the SQL dump inside a full backup. It is written last so that a
+/// restore has staged every attachment before touching the database.
+pub const DUMP_ENTRY: &str = "database.sql";
+/// Attachments are stored under the same app-data-relative paths the database references.
+pub const MEDICAL_FILES_PREFIX: &str = "medical_files/";
+
+/// Relative paths of every stored medical file plus the legacy paths still
+/// referenced by `ecg_ett_exams`, deduplicated.
+pub async fn referenced_files(config: &DbConfig) -> Result<Vec<String>, String> {
+    let mut conn = db_config::connect(config).await?;
+    let rows: Vec<(Option<String>, Option<String>)> =
+        sqlx::query_as("SELECT ecg_files, ett_files FROM ecg_ett_exams")
+            .fetch_all(&mut conn)
+            .await
+            .map_err(|e| format!("Failed to list medical attachments: {}", e))?;
+    let stored: Vec<String> = sqlx::query_scalar("SELECT DISTINCT sha256 FROM medical_files")
+        .fetch_all(&mut conn)
+        .await
+        .map_err(|e| format!("Failed to list medical attachments: {}", e))?;
+
+    // A malformed checksum cannot name a stored file; the rest is still backed up
+    let stored = stored
+        .iter()
+        .filter_map(|sha256| match file_storage::relative_path(sha256) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                log::warn!("Skipping medical file: {}", e);
+                None
+            }
+        });
+    let files: BTreeSet<String> = rows
+        .into_iter()
+        .flat_map(|(ecg, ett)| [ecg, ett])
+        .flatten()
+        .flat_map(|json| listed_paths(&json))
+        .chain(stored)
+        .collect();
+    Ok(files.into_iter().collect())
+}
+
+/// Legacy paths in an exam's JSON file list. Lists written by older versions
+/// can mix `medical_files` ids with paths that failed to import.
+fn listed_paths(json: &str) -> Vec<String> {
+    serde_json::from_str::<Vec<Value>>(json)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|entry| entry.as_str().map(str::to_string))
+        .filter(|path| path.starts_with(MEDICAL_FILES_PREFIX))
+        .collect()
+}
+
+/// Counts the bytes going into the compressor, i.e. the archive's uncompressed size.
+struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes a tar of the attachments followed by the dump at `dump_path`.
+/// Returns the uncompressed archive size and the referenced files that no
+/// longer exist on disk.
+pub fn write<W: Write>(
+    writer: &mut W,
+    app_dir: &Path,
+    files: &[String],
+    dump_path: &Path,
+) -> Result<(u64, Vec<String>), String> {
+    let mut builder = tar::Builder::new(CountingWriter {
+        inner: writer,
+        count: 0,
+    });
+    let mut missing = Vec::new();
+
+    for relative in files {
+        let path = safe_path::resolve(app_dir, relative)?;
+        if !path.is_file() {
+            missing.push(relative.clone());
+            continue;
+        }
+        builder
+            .append_path_with_name(&path, relative)
+            .map_err(|e| format!("Failed to archive {}: {}", relative, e))?;
+    }
+
+    builder
+        .append_path_with_name(dump_path, DUMP_ENTRY)
+        .map_err(|e| format!("Failed to archive database dump: {}", e))?;
+
+    let counter = builder
+        .into_inner()
+        .map_err(|e| format!("Failed to write backup file: {}", e))?;
+    Ok((counter.count, missing))
+}
+
+/// Extracts the attachments to a staging folder, hands the dump to
+/// `restore_dump`, and only moves the attachments into place once the
+/// database restore succeeded. Without `app_dir` only the dump is restored.
+pub fn restore<R: Read>(
+    app_dir: Option<&Path>,
+    reader: R,
+    restore_dump: impl FnOnce(&mut dyn BufRead) -> Result<(), String>,
+) -> Result<usize, String> {
+    // Staged next to the live files so the final move is a rename
+    let staging = app_dir
+        .map(tempfile::tempdir_in)
+        .transpose()
+        .map_err(|e| format!("Failed to create staging directory: {}", e))?;
+    let mut staged = Vec::new();
+    let mut restore_dump = Some(restore_dump);
+
+    let mut archive = tar::Archive::new(reader);
+    let entries = archive
+        .entries()
+        .map_err(|e| format!("Failed to read backup archive: {}", e))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("Failed to read backup archive: {}", e))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry
+            .path()
+            .map_err(|e| format!("Invalid archive entry: {}", e))?
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        if name == DUMP_ENTRY {
+            let restore_dump = restore_dump
+                .take()
+                .ok_or_else(|| "Backup archive contains more than one dump".to_string())?;
+            restore_dump(&mut BufReader::new(&mut entry))?;
+            continue;
+        }
+
+        let Some(staging) = &staging else {
+            continue;
+        };
+        // Anything else in app data (settings, backups) is off limits
+        if !name.starts_with(MEDICAL_FILES_PREFIX) {
+            return Err(format!("Unexpected file in backup archive: {}", name));
+        }
+        let target = safe_path::resolve(staging.path(), &name)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create staging directory: {}", e))?;
+        }
+        let mut file =
+            File::create(&target).map_err(|e| format!("Failed to extract {}: {}", name, e))?;
+        io::copy(&mut entry, &mut file)
+            .map_err(|e| format!("Failed to extract {}: {}", name, e))?;
+        staged.push(name);
+    }
+
+    if restore_dump.is_some() {
+        return Err("Backup archive does not contain a database dump".to_string());
+    }
+
+    let (Some(app_dir), Some(staging)) = (app_dir, staging) else {
+        return Ok(0);
+    };
+    for name in &staged {
+        let target = safe_path::resolve(app_dir, name)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create medical files directory: {}", e))?;
+        }
+        fs::rename(staging.path().join(name), &target)
+            .map_err(|e| format!("Failed to restore {}: {}", name, e))?;
+    }
+    Ok(staged.len())
+}
+
+/// Extracts only the attachments listed in `files` into `app_dir`, leaving
+/// the dump alone. Files already there are kept. Returns how many were
+/// written.
+pub fn extract<R: Read>(
+    app_dir: &Path,
+    reader: R,
+    files: &BTreeSet<String>,
+) -> Result<usize, String> {
+    let mut extracted = 0;
+    let mut archive = tar::Archive::new(reader);
+    let entries = archive
+        .entries()
+        .map_err(|e| format!("Failed to read backup archive: {}", e))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("Failed to read backup archive: {}", e))?;
+        let name = entry
+            .path()
+            .map_err(|e| format!("Invalid archive entry: {}", e))?
+            .to_string_lossy()
+            .replace('\\', "/");
+        if !entry.header().entry_type().is_file() || !files.contains(&name) {
+            continue;
+        }
+
+        let target = safe_path::resolve(app_dir, &name)?;
+        let dir = target.parent().unwrap_or(app_dir);
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create medical files directory: {}", e))?;
+        let mut staged = tempfile::NamedTempFile::new_in(dir)
+            .map_err(|e| format!("Failed to extract {}: {}", name, e))?;
+        io::copy(&mut entry, &mut staged)
+            .map_err(|e| format!("Failed to extract {}: {}", name, e))?;
+        match staged.persist_noclobber(&target) {
+            Ok(_) => extracted += 1,
+            Err(e) if e.error.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(format!("Failed to restore {}: {}", name, e.error)),
+        }
+    }
+    Ok(extracted)
+}
+
+/// Runs `check` over the dump inside an archive; `None` when there is no dump.
+pub fn check_dump<R: Read, T>(
+    reader: R,
+    check: impl FnOnce(&mut dyn BufRead) -> io::Result<T>,
+) -> io::Result<Option<T>> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.to_string_lossy() == DUMP_ENTRY {
+            return check(&mut BufReader::new(&mut entry)).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STORED: &str = "medical_files/ab/abcdef.pdf";
+    const DUMP: &str = "-- MySQL dump 10.13\nSELECT 1;\n-- Dump completed\n";
+
+    /// A full backup of one stored file, written from a scratch app data folder.
+    fn full_backup() -> Vec<u8> {
+        let source = tempfile::tempdir().unwrap();
+        fs::create_dir_all(source.path().join("medical_files/ab")).unwrap();
+        fs::write(source.path().join(STORED), b"%PDF-1.7").unwrap();
+        let dump_path = source.path().join("dump.sql");
+        fs::write(&dump_path, DUMP).unwrap();
+
+        let files = [
+            STORED.to_string(),
+            "medical_files/cd/missing.pdf".to_string(),
+        ];
+        let mut archive = Vec::new();
+        let (size, missing) = write(&mut archive, source.path(), &files, &dump_path).unwrap();
+        assert_eq!(size, archive.len() as u64);
+        assert_eq!(missing, ["medical_files/cd/missing.pdf"]);
+        archive
+    }
+
+    /// An archive holding a single entry named `name` verbatim, which
+    /// `tar::Builder` would refuse to write for `..` or absolute names.
+    fn raw_archive(name: &str) -> Vec<u8> {
+        let mut header = tar::Header::new_gnu();
+        header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_size(4);
+        header.set_mode(0o644);
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_cksum();
+        let mut builder = tar::Builder::new(Vec::new());
+        builder.append(&header, &b"evil"[..]).unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn lists_legacy_paths_next_to_file_ids() {
+        assert_eq!(
+            listed_paths(r#"[12, "medical_files/ecg.pdf", "uploads/other.pdf", null]"#),
+            ["medical_files/ecg.pdf"]
+        );
+        assert!(listed_paths("not json").is_empty());
+    }
+
+    #[test]
+    fn restores_the_dump_and_attachments() {
+        let archive = full_backup();
+        let app_dir = tempfile::tempdir().unwrap();
+        let mut dump = String::new();
+        let restored = restore(Some(app_dir.path()), &archive[..], |reader| {
+            reader
+                .read_to_string(&mut dump)
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(restored, 1);
+        assert_eq!(dump, DUMP);
+        assert_eq!(fs::read(app_dir.path().join(STORED)).unwrap(), b"%PDF-1.7");
+    }
+
+    #[test]
+    fn leaves_attachments_alone_when_the_dump_fails() {
+        let archive = full_backup();
+        let app_dir = tempfile::tempdir().unwrap();
+        let result = restore(Some(app_dir.path()), &archive[..], |_| {
+            Err("Restore failed".to_string())
+        });
+
+        assert_eq!(result.unwrap_err(), "Restore failed");
+        assert!(!app_dir.path().join(STORED).exists());
+    }
+
+    #[test]
+    fn extracts_listed_attachments_and_checks_the_dump() {
+        let archive = full_backup();
+        let app_dir = tempfile::tempdir().unwrap();
+        let files = BTreeSet::from([STORED.to_string()]);
+        assert_eq!(extract(app_dir.path(), &archive[..], &files).unwrap(), 1);
+        assert_eq!(fs::read(app_dir.path().join(STORED)).unwrap(), b"%PDF-1.7");
+        // Files already there are kept
+        assert_eq!(extract(app_dir.path(), &archive[..], &files).unwrap(), 0);
+
+        let dump = check_dump(&archive[..], |reader| {
+            let mut dump = String::new();
+            reader.read_to_string(&mut dump).map(|_| dump)
+        })
+        .unwrap();
+        assert_eq!(dump.as_deref(), Some(DUMP));
+    }
+
+    #[test]
+    fn rejects_entries_escaping_the_app_folder() {
+        let parent = tempfile::tempdir().unwrap();
+        let app_dir = parent.path().join("app");
+        fs::create_dir(&app_dir).unwrap();
+
+        for name in [
+            "medical_files/../../evil.txt",
+            "/medical_files/evil.txt",
+            "/tmp/evil.txt",
+        ] {
+            let archive = raw_archive(name);
+            assert!(
+                restore(Some(&app_dir), &archive[..], |_| Ok(())).is_err(),
+                "{}",
+                name
+            );
+            let files = BTreeSet::from([name.to_string()]);
+            assert!(extract(&app_dir, &archive[..], &files).is_err(), "{}", name);
+        }
+        assert!(!parent.path().join("evil.txt").exists());
+        assert!(!app_dir.join("evil.txt").exists());
+        assert!(!app_dir.join("medical_files").exists());
+    }
+}
@@ -0,0 +1,187 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use tempfile::NamedTempFile;
+
+use super::job::JobToken;
+use super::{table_marker, PROGRESS_INTERVAL_BYTES};
+use crate::db_config::DbConfig;
+
+pub const MYSQLDUMP: &str = "mysqldump";
+pub const MYSQL: &str = "mysql";
+
+pub fn is_available(binary: &str) -> bool {
+    Command::new(binary)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Writes a `[client]` option file for `--defaults-extra-file` so the password
+/// never shows up in process listings. The file is removed when dropped.
+fn client_option_file(config: &DbConfig) -> Result<NamedTempFile, String> {
+    let mut file = tempfile::Builder::new()
+        .prefix("cardiopc-client-")
+        .suffix(".cnf")
+        .tempfile()
+        .map_err(|e| format!("Failed to create MySQL option file: {}", e))?;
+
+    let escape = |value: &str| value.replace('\\', "\\\\").replace('"', "\\\"");
+    writeln!(
+        file,
+        "[client]\nhost=\"{}\"\nport={}\nuser=\"{}\"\npassword=\"{}\"",
+        escape(&config.host),
+        config.port,
+        escape(&config.user),
+        escape(&config.password)
+    )
+    .and_then(|_| file.flush())
+    .map_err(|e| format!("Failed to write MySQL option file: {}", e))?;
+
+    Ok(file)
+}
+
+fn defaults_extra_file_arg(file: &NamedTempFile) -> String {
+    format!("--defaults-extra-file={}", file.path().display())
+}
+
+/// Drains stderr on its own thread so a chatty child cannot block on a full pipe.
+fn collect_stderr(child: &mut Child) -> thread::JoinHandle<String> {
+    let stderr = child.stderr.take();
+    thread::spawn(move || {
+        let mut buf = String::new();
+        if let Some(mut stderr) = stderr {
+            let _ = stderr.read_to_string(&mut buf);
+        }
+        buf
+    })
+}
+
+/// Copies a dump stream line by line, reporting progress every
+/// `PROGRESS_INTERVAL_BYTES` and whenever a new table section starts.
+fn stream_dump<R: BufRead, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    mut report: impl FnMut(u64, u32, Option<String>),
+) -> io::Result<u64> {
+    let mut line = Vec::with_capacity(64 * 1024);
+    let mut bytes = 0u64;
+    let mut last_report = 0u64;
+    let mut tables_done = 0u32;
+
+    loop {
+        line.clear();
+        let n = reader.read_until(b'\n', &mut line)?;
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&line)?;
+        bytes += n as u64;
+
+        if let Some(table) = table_marker(&line) {
+            tables_done += 1;
+            report(bytes, tables_done, Some(table));
+            last_report = bytes;
+        } else if bytes - last_report >= PROGRESS_INTERVAL_BYTES {
+            report(bytes, tables_done, None);
+            last_report = bytes;
+        }
+    }
+
+    writer.flush()?;
+    report(bytes, tables_done, None);
+    Ok(bytes)
+}
+
+pub fn dump<W: Write>(
+    config: &DbConfig,
+    writer: &mut W,
+    report: impl FnMut(u64, u32, Option<String>),
+    job: &JobToken,
+) -> Result<u64, String> {
+    // Execute mysqldump command; --defaults-extra-file must come first
+    let option_file = client_option_file(config)?;
+    let mut child = Command::new(MYSQLDUMP)
+        .arg(defaults_extra_file_arg(&option_file))
+        .args([
+            "--single-transaction",
+            "--routines",
+            "--triggers",
+            &config.database,
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute mysqldump: {}. Make sure MySQL is installed and mysqldump is in your PATH.", e))?;
+
+    let stderr = collect_stderr(&mut child);
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| "Failed to capture mysqldump output".to_string())?;
+
+    // Killing the child on cancel closes stdout, which ends the copy
+    let child = job.attach(child);
+    let copied = stream_dump(&mut BufReader::new(stdout), writer, report);
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to wait for mysqldump process: {}", e))?;
+    let error = stderr.join().unwrap_or_default();
+
+    job.check()?;
+    if !status.success() {
+        return Err(format!("Backup failed: {}", error));
+    }
+
+    copied.map_err(|e| format!("Failed to write backup file: {}", e))
+}
+
+pub fn restore<R: BufRead>(
+    config: &DbConfig,
+    reader: &mut R,
+    report: impl FnMut(u64, u32, Option<String>),
+    job: &JobToken,
+) -> Result<u64, String> {
+    // Execute mysql command to restore
+    let option_file = client_option_file(config)?;
+    let mut child = Command::new(MYSQL)
+        .arg(defaults_extra_file_arg(&option_file))
+        .arg(&config.database)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            format!(
+                "Failed to execute mysql: {}. Make sure MySQL client is installed.",
+                e
+            )
+        })?;
+
+    let stderr = collect_stderr(&mut child);
+    let stdin = child.stdin.take();
+    let child = job.attach(child);
+    let copied = match stdin {
+        Some(mut stdin) => stream_dump(reader, &mut stdin, report),
+        None => Err(io::Error::new(
+            io::ErrorKind::BrokenPipe,
+            "stdin not captured",
+        )),
+    };
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to wait for mysql process: {}", e))?;
+    let error = stderr.join().unwrap_or_default();
+
+    job.check()?;
+    if !status.success() {
+        return Err(format!("Restore failed: {}", error));
+    }
+
+    copied.map_err(|e| format!("Failed to write to mysql stdin: {}", e))
+}
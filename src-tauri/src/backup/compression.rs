@@ -0,0 +1,206 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use super::encryption::OutputFile;
+
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+
+    /// Splits a trailing `.gz`/`.zst` off a backup filename, returning the inner name.
+    pub fn strip_extension(filename: &str) -> (&str, Self) {
+        [Compression::Gzip, Compression::Zstd]
+            .into_iter()
+            .find_map(|c| filename.strip_suffix(c.extension()).map(|inner| (inner, c)))
+            .unwrap_or((filename, Compression::None))
+    }
+}
+
+pub enum BackupWriter {
+    Plain(OutputFile),
+    Gzip(GzEncoder<OutputFile>),
+    Zstd(zstd::Encoder<'static, OutputFile>),
+}
+
+impl BackupWriter {
+    pub fn create(
+        path: &Path,
+        compression: Compression,
+        passphrase: Option<&str>,
+    ) -> io::Result<Self> {
+        let file = OutputFile::create(path, passphrase)?;
+        Ok(match compression {
+            Compression::None => BackupWriter::Plain(file),
+            Compression::Gzip => {
+                BackupWriter::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            }
+            Compression::Zstd => BackupWriter::Zstd(zstd::Encoder::new(file, ZSTD_LEVEL)?),
+        })
+    }
+
+    /// Writes the compression trailer and flushes; dropping without this leaves a truncated archive.
+    pub fn finish(self) -> io::Result<()> {
+        let inner = match self {
+            BackupWriter::Plain(w) => w,
+            BackupWriter::Gzip(w) => w.finish()?,
+            BackupWriter::Zstd(w) => w.finish()?,
+        };
+        inner.finish()
+    }
+}
+
+impl Write for BackupWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            BackupWriter::Plain(w) => w.write(buf),
+            BackupWriter::Gzip(w) => w.write(buf),
+            BackupWriter::Zstd(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            BackupWriter::Plain(w) => w.flush(),
+            BackupWriter::Gzip(w) => w.flush(),
+            BackupWriter::Zstd(w) => w.flush(),
+        }
+    }
+}
+
+pub fn open_reader(
+    input: Box<dyn Read + Send>,
+    compression: Compression,
+) -> io::Result<Box<dyn BufRead + Send>> {
+    Ok(match compression {
+        Compression::None => Box::new(BufReader::new(input)),
+        Compression::Gzip => Box::new(BufReader::new(GzDecoder::new(BufReader::new(input)))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::new(input)?)),
+    })
+}
+
+/// Size of the dump or archive once decompressed. Gzip records it (mod 4 GiB) in its
+/// trailer; zstd streams do not, so those are decoded to count the bytes.
+/// Only valid for unencrypted files.
+pub fn uncompressed_size(path: &Path, compression: Compression) -> io::Result<u64> {
+    match compression {
+        Compression::None => Ok(path.metadata()?.len()),
+        Compression::Gzip => {
+            let mut file = File::open(path)?;
+            file.seek(SeekFrom::End(-4))?;
+            let mut trailer = [0u8; 4];
+            file.read_exact(&mut trailer)?;
+            Ok(u32::from_le_bytes(trailer) as u64)
+        }
+        Compression::Zstd => {
+            let input: Box<dyn Read + Send> = Box::new(File::open(path)?);
+            io::copy(&mut open_reader(input, compression)?, &mut io::sink())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &[u8] = b"-- MySQL dump 10.13\nINSERT INTO `patients` VALUES (1,'Dupont');\n";
+
+    fn write_backup(path: &Path, compression: Compression, data: &[u8]) {
+        let mut writer = BackupWriter::create(path, compression, None).unwrap();
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap();
+    }
+
+    fn read_backup(path: &Path, compression: Compression) -> Vec<u8> {
+        let input: Box<dyn Read + Send> = Box::new(File::open(path).unwrap());
+        let mut data = Vec::new();
+        open_reader(input, compression)
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn round_trips_every_compression() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = DUMP.repeat(1000);
+        for compression in [Compression::None, Compression::Gzip, Compression::Zstd] {
+            let path = dir
+                .path()
+                .join(format!("backup.sql{}", compression.extension()));
+            write_backup(&path, compression, &data);
+
+            assert_eq!(read_backup(&path, compression), data, "{:?}", compression);
+            assert_eq!(
+                uncompressed_size(&path, compression).unwrap(),
+                data.len() as u64,
+                "{:?}",
+                compression
+            );
+        }
+    }
+
+    #[test]
+    fn compressed_output_is_smaller_and_not_plain_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = DUMP.repeat(1000);
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let path = dir.path().join("backup");
+            write_backup(&path, compression, &data);
+            let written = std::fs::read(&path).unwrap();
+            assert!(written.len() < data.len() / 10, "{:?}", compression);
+            assert!(!written.starts_with(b"-- MySQL dump"), "{:?}", compression);
+        }
+    }
+
+    #[test]
+    fn detects_compression_from_the_extension() {
+        assert_eq!(
+            Compression::strip_extension("cardio_backup_20240115_093012.sql.gz"),
+            ("cardio_backup_20240115_093012.sql", Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::strip_extension("cardio_backup_20240115_093012.tar.zst"),
+            ("cardio_backup_20240115_093012.tar", Compression::Zstd)
+        );
+        assert_eq!(
+            Compression::strip_extension("cardio_backup_20240115_093012.sql"),
+            ("cardio_backup_20240115_093012.sql", Compression::None)
+        );
+    }
+
+    #[test]
+    fn rejects_data_that_is_not_compressed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("backup.sql.zst");
+        write_backup(&path, Compression::None, DUMP);
+
+        let input: Box<dyn Read + Send> = Box::new(File::open(&path).unwrap());
+        let mut data = Vec::new();
+        let read = open_reader(input, Compression::Zstd).and_then(|mut r| r.read_to_end(&mut data));
+        assert!(read.is_err());
+        let input: Box<dyn Read + Send> = Box::new(File::open(&path).unwrap());
+        let read = open_reader(input, Compression::Gzip).and_then(|mut r| r.read_to_end(&mut data));
+        assert!(read.is_err());
+    }
+}
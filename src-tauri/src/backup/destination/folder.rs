@@ -0,0 +1,118 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::BackupDestination;
+
+pub struct FolderDestination {
+    root: PathBuf,
+}
+
+impl FolderDestination {
+    pub fn new(path: &str) -> Self {
+        FolderDestination {
+            root: PathBuf::from(path.trim()),
+        }
+    }
+
+    fn ensure_root(&self) -> Result<(), String> {
+        if self.root.as_os_str().is_empty() || !self.root.is_absolute() {
+            return Err(format!(
+                "Destination folder must be an absolute path: {}",
+                self.root.display()
+            ));
+        }
+        fs::create_dir_all(&self.root)
+            .map_err(|e| format!("Failed to create {}: {}", self.root.display(), e))
+    }
+}
+
+impl BackupDestination for FolderDestination {
+    fn upload(&self, local_path: &Path, name: &str) -> Result<(), String> {
+        self.ensure_root()?;
+
+        // Copy under a temporary name so a dropped share never leaves a
+        // truncated file that looks like a complete backup
+        let partial = self.root.join(format!("{}.part", name));
+        let target = self.root.join(name);
+        fs::copy(local_path, &partial)
+            .and_then(|_| fs::rename(&partial, &target))
+            .map_err(|e| {
+                let _ = fs::remove_file(&partial);
+                format!("Failed to copy {} to {}: {}", name, self.root.display(), e)
+            })?;
+        Ok(())
+    }
+
+    fn check(&self) -> Result<String, String> {
+        self.ensure_root()?;
+        tempfile::tempfile_in(&self.root)
+            .map_err(|e| format!("{} is not writable: {}", self.root.display(), e))?;
+        Ok(format!("{} is writable", self.root.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn uploads_and_replaces_backups() {
+        let source = tempfile::tempdir().unwrap();
+        let share = tempfile::tempdir().unwrap();
+        let root = share.path().join("nested").join("backups");
+        let destination = FolderDestination::new(root.to_str().unwrap());
+
+        let backup = source.path().join("backup.sql");
+        fs::write(&backup, "first").unwrap();
+        destination.upload(&backup, "backup.sql").unwrap();
+        fs::write(&backup, "second").unwrap();
+        destination.upload(&backup, "backup.sql").unwrap();
+        destination
+            .upload(&backup, "backup.sql.manifest.json")
+            .unwrap();
+
+        assert_eq!(names(&root), vec!["backup.sql", "backup.sql.manifest.json"]);
+        assert_eq!(
+            fs::read_to_string(root.join("backup.sql")).unwrap(),
+            "second"
+        );
+
+        fs::remove_file(root.join("backup.sql")).unwrap();
+        assert_eq!(names(&root), vec!["backup.sql.manifest.json"]);
+    }
+
+    #[test]
+    fn failed_upload_leaves_no_partial_file() {
+        let share = tempfile::tempdir().unwrap();
+        let destination = FolderDestination::new(share.path().to_str().unwrap());
+
+        let missing = share.path().join("missing.sql");
+        assert!(destination.upload(&missing, "backup.sql").is_err());
+        assert!(names(share.path()).is_empty());
+    }
+
+    #[test]
+    fn checks_the_folder_is_writable() {
+        let share = tempfile::tempdir().unwrap();
+        let destination = FolderDestination::new(share.path().to_str().unwrap());
+        assert!(destination.check().is_ok());
+        assert!(names(share.path()).is_empty());
+    }
+
+    #[test]
+    fn rejects_relative_folders() {
+        for path in ["", "backups", "./backups"] {
+            let destination = FolderDestination::new(path);
+            assert!(destination.check().is_err(), "{:?}", path);
+        }
+    }
+}
@@ -0,0 +1,300 @@
+mod folder;
+mod s3;
+mod sftp;
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{command, AppHandle};
+
+use super::job::JobToken;
+use super::BackupError;
+use crate::{config_file, secrets};
+
+const DESTINATIONS_FILE: &str = "backup_destinations.json";
+/// Uploads run while the backup job holds its slot, so a server that stops
+/// answering must not hang them.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const IO_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A place backups are copied to after being written locally.
+pub trait BackupDestination {
+    /// Copies `local_path` to the destination under `name`, replacing any existing copy.
+    fn upload(&self, local_path: &Path, name: &str) -> Result<(), String>;
+
+    /// Checks that the destination is reachable and writable, returning a short
+    /// description for the settings screen.
+    fn check(&self) -> Result<String, String>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum DestinationConfig {
+    /// Local folder, mounted drive or network share (UNC path such as `\\nas\backups`).
+    Folder {
+        path: String,
+    },
+    Sftp(sftp::SftpConfig),
+    S3(s3::S3Config),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Destination {
+    /// Keys the destination's credentials in the keyring. Assigned when the
+    /// destination is first saved and kept across renames; empty for
+    /// destinations saved by older versions, whose credentials are keyed by name.
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub config: DestinationConfig,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Destination {
+    /// Prefix of the keyring accounts holding this destination's credentials.
+    fn secrets_key(&self) -> &str {
+        if self.id.is_empty() {
+            &self.name
+        } else {
+            &self.id
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadResult {
+    pub destination: String,
+    pub error: Option<String>,
+}
+
+impl DestinationConfig {
+    fn build(&self) -> Box<dyn BackupDestination + '_> {
+        match self {
+            DestinationConfig::Folder { path } => Box::new(folder::FolderDestination::new(path)),
+            DestinationConfig::Sftp(config) => Box::new(sftp::SftpDestination::new(config)),
+            DestinationConfig::S3(config) => Box::new(s3::S3Destination::new(config)),
+        }
+    }
+}
+
+impl DestinationConfig {
+    /// Credential fields, keyed by the name they are stored under in the keyring.
+    fn secrets_mut(&mut self) -> Vec<(&'static str, &mut Option<String>)> {
+        match self {
+            DestinationConfig::Folder { .. } => Vec::new(),
+            DestinationConfig::Sftp(config) => vec![
+                ("password", &mut config.password),
+                ("private_key_passphrase", &mut config.private_key_passphrase),
+            ],
+            DestinationConfig::S3(config) => vec![("secret_key", &mut config.secret_key)],
+        }
+    }
+
+    /// Fills credentials left out of the saved file from the keyring
+    /// accounts under `key`.
+    fn resolve_secrets(&mut self, key: &str) -> Result<(), String> {
+        for (field, value) in self.secrets_mut() {
+            if value.is_none() {
+                *value = secrets::get(&secret_account(key, field))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the keyring accounts under `key`.
+    fn delete_secrets(&self, key: &str) {
+        for (field, _) in self.clone().secrets_mut() {
+            if let Err(e) = secrets::delete(&secret_account(key, field)) {
+                log::warn!("{}", e);
+            }
+        }
+    }
+}
+
+fn secret_account(key: &str, field: &str) -> String {
+    format!("backup-destination/{}/{}", key, field)
+}
+
+/// Destinations as saved; credentials held in the keyring are `None`.
+fn load(app: &AppHandle) -> Result<Vec<Destination>, String> {
+    Ok(config_file::load(app, DESTINATIONS_FILE)?.unwrap_or_default())
+}
+
+/// Destinations with their credentials, for uploading. Blocking.
+fn load_resolved(app: &AppHandle) -> Result<Vec<Destination>, String> {
+    let mut destinations = load(app)?;
+    for destination in &mut destinations {
+        let key = destination.secrets_key().to_string();
+        destination.config.resolve_secrets(&key)?;
+    }
+    Ok(destinations)
+}
+
+/// Moves the credentials of `destination` into the keyring. A `None` field
+/// keeps what was saved before and an empty one clears it. Where there is no
+/// usable keyring the credential stays in the settings file.
+fn store_secrets(destination: &mut Destination, previous: Option<&Destination>) {
+    let mut previous = previous.map(|d| {
+        let mut config = d.config.clone();
+        // Credentials of older versions are keyed by name and move to the id
+        if d.secrets_key() != destination.secrets_key() {
+            if let Err(e) = config.resolve_secrets(d.secrets_key()) {
+                log::warn!("{}", e);
+            }
+        }
+        config
+    });
+    let mut saved = previous
+        .as_mut()
+        .map(DestinationConfig::secrets_mut)
+        .unwrap_or_default();
+
+    for (field, value) in destination.config.secrets_mut() {
+        let account = secret_account(&destination.id, field);
+        // Also moves credentials still in the file from older versions
+        let secret = value.take().or_else(|| {
+            saved
+                .iter_mut()
+                .find(|(name, _)| *name == field)
+                .and_then(|(_, old)| old.take())
+        });
+        match secret {
+            None => {}
+            Some(secret) if secret.is_empty() => {
+                if let Err(e) = secrets::delete(&account) {
+                    log::warn!("{}", e);
+                }
+            }
+            Some(secret) => {
+                if let Err(e) = secrets::set(&account, &secret) {
+                    log::warn!("{}; keeping it in the settings file", e);
+                    *value = Some(secret);
+                }
+            }
+        }
+    }
+}
+
+/// Copies a finished backup and its manifest to every enabled destination.
+/// Blocking; a failing destination does not stop the others. Cancelling the
+/// job skips the files not uploaded yet.
+pub fn upload_all(app: &AppHandle, files: &[PathBuf], job: &JobToken) -> Vec<UploadResult> {
+    let destinations = match load_resolved(app) {
+        Ok(destinations) => destinations,
+        Err(e) => {
+            return vec![UploadResult {
+                destination: String::new(),
+                error: Some(e),
+            }]
+        }
+    };
+
+    destinations
+        .iter()
+        .filter(|d| d.enabled)
+        .map(|destination| {
+            let target = destination.config.build();
+            let error = files
+                .iter()
+                .try_for_each(|path| {
+                    job.check()?;
+                    let name = path
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .ok_or_else(|| format!("Invalid backup path: {}", path.display()))?;
+                    target.upload(path, name)
+                })
+                .err();
+            if let Some(e) = &error {
+                log::warn!("Upload to {} failed: {}", destination.name, e);
+            }
+            UploadResult {
+                destination: destination.name.clone(),
+                error,
+            }
+        })
+        .collect()
+}
+
+/// Saved destinations without their credentials.
+#[command]
+pub fn get_backup_destinations(app: AppHandle) -> Result<Vec<Destination>, BackupError> {
+    let mut destinations = load(&app)?;
+    for destination in &mut destinations {
+        for (_, value) in destination.config.secrets_mut() {
+            *value = None;
+        }
+    }
+    Ok(destinations)
+}
+
+#[command]
+pub fn set_backup_destinations(
+    app: AppHandle,
+    mut destinations: Vec<Destination>,
+) -> Result<(), BackupError> {
+    for destination in &destinations {
+        if destination.name.trim().is_empty() {
+            return Err("Backup destination name is required".to_string().into());
+        }
+    }
+
+    let previous = load(&app)?;
+    let mut matched = Vec::new();
+    let mut ids = Vec::new();
+    for destination in &mut destinations {
+        let before = previous.iter().position(|d| {
+            d.id == destination.id && (!d.id.is_empty() || d.name == destination.name)
+        });
+        // Ids are only ever assigned here, so a destination re-created under
+        // an old name, or copied from another, never inherits old credentials
+        let before = before.filter(|i| !matched.contains(i));
+        if before.is_none() || destination.id.is_empty() {
+            destination.id = new_id();
+        }
+        matched.extend(before);
+        ids.push(destination.id.clone());
+        store_secrets(destination, before.map(|i| &previous[i]));
+    }
+    // Credentials of removed destinations and those still keyed by name
+    for old in &previous {
+        if !ids.iter().any(|id| id == old.secrets_key()) {
+            old.config.delete_secrets(old.secrets_key());
+        }
+    }
+
+    Ok(config_file::save(&app, DESTINATIONS_FILE, &destinations)?)
+}
+
+fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Checks a destination as entered. Credentials left empty are taken from
+/// the saved destination with the given `id`, if any.
+#[command]
+pub async fn test_backup_destination(
+    app: AppHandle,
+    destination: DestinationConfig,
+    id: Option<String>,
+) -> Result<String, BackupError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut destination = destination;
+        if let Some(id) = id.filter(|id| !id.is_empty()) {
+            if let Some(saved) = load(&app)?.iter().find(|d| d.id == id) {
+                destination.resolve_secrets(saved.secrets_key())?;
+            }
+        }
+        let result = destination.build().check();
+        result
+    })
+    .await
+    .map_err(|e| format!("Destination check failed: {}", e))?
+    .map_err(BackupError::from)
+}
@@ -0,0 +1,304 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::path::Path;
+use url::Url;
+
+use super::{BackupDestination, CONNECT_TIMEOUT, IO_TIMEOUT};
+use crate::backup::manifest;
+
+/// Characters left unescaped in SigV4 canonical URIs.
+const URI_UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// Any S3-compatible object store: AWS, MinIO, Wasabi, Garage...
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct S3Config {
+    /// Base URL, e.g. `https://s3.eu-west-3.amazonaws.com` or `http://minio.local:9000`.
+    pub endpoint: String,
+    #[serde(default = "default_region")]
+    pub region: String,
+    pub bucket: String,
+    /// Key prefix such as `cardiopc/`.
+    #[serde(default)]
+    pub prefix: String,
+    pub access_key: String,
+    /// Kept in the OS keyring once saved, like the SFTP password.
+    #[serde(default)]
+    pub secret_key: Option<String>,
+    /// `endpoint/bucket/key` addressing, required by MinIO and most self-hosted stores.
+    #[serde(default = "default_path_style")]
+    pub path_style: bool,
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_path_style() -> bool {
+    true
+}
+
+pub struct S3Destination<'a> {
+    config: &'a S3Config,
+    agent: ureq::Agent,
+}
+
+impl<'a> S3Destination<'a> {
+    pub fn new(config: &'a S3Config) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(CONNECT_TIMEOUT)
+            .timeout_read(IO_TIMEOUT)
+            .timeout_write(IO_TIMEOUT)
+            .build();
+        S3Destination { config, agent }
+    }
+
+    /// URL of `key` in the bucket; an empty key addresses the bucket itself.
+    fn object_url(&self, key: &str) -> Result<Url, String> {
+        let config = self.config;
+        let mut url = Url::parse(config.endpoint.trim())
+            .map_err(|e| format!("Invalid S3 endpoint '{}': {}", config.endpoint, e))?;
+        if !config.path_style {
+            let host = url
+                .host_str()
+                .ok_or_else(|| format!("Invalid S3 endpoint '{}'", config.endpoint))?;
+            let host = format!("{}.{}", config.bucket, host);
+            url.set_host(Some(&host))
+                .map_err(|e| format!("Invalid S3 bucket '{}': {}", config.bucket, e))?;
+        }
+
+        let mut segments = Vec::new();
+        if config.path_style {
+            segments.push(config.bucket.as_str());
+        }
+        segments.extend(key.split('/').filter(|s| !s.is_empty()));
+        let path: Vec<String> = segments
+            .iter()
+            .map(|s| utf8_percent_encode(s, URI_UNRESERVED).to_string())
+            .collect();
+        url.set_path(&format!("/{}", path.join("/")));
+        Ok(url)
+    }
+
+    /// SigV4 headers for a request without a query string.
+    fn sign(
+        &self,
+        method: &str,
+        url: &Url,
+        payload_sha256: &str,
+    ) -> Result<Vec<(&'static str, String)>, String> {
+        let config = self.config;
+        let secret_key = config
+            .secret_key
+            .as_deref()
+            .ok_or_else(|| "S3 secret key is required".to_string())?;
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let headers = [
+            ("host", host.as_str()),
+            ("x-amz-content-sha256", payload_sha256),
+            ("x-amz-date", amz_date.as_str()),
+        ];
+        let (signed_headers, canonical_request) =
+            canonical_request(method, url.path(), &headers, payload_sha256);
+
+        let scope = format!("{}/{}/s3/aws4_request", date, config.region);
+        let key = signing_key(secret_key, &date, &config.region, "s3");
+        let signature = signature(&key, &amz_date, &scope, &canonical_request);
+
+        Ok(vec![
+            ("x-amz-date", amz_date),
+            ("x-amz-content-sha256", payload_sha256.to_string()),
+            (
+                "Authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                    config.access_key, scope, signed_headers, signature
+                ),
+            ),
+        ])
+    }
+
+    fn request(
+        &self,
+        method: &str,
+        url: &Url,
+        payload_sha256: &str,
+    ) -> Result<ureq::Request, String> {
+        Ok(self.sign(method, url, payload_sha256)?.into_iter().fold(
+            self.agent.request_url(method, url),
+            |request, (name, value)| request.set(name, &value),
+        ))
+    }
+}
+
+/// Canonical request for `headers`, which must be lowercase and sorted by
+/// name, returned with the matching `SignedHeaders` value.
+fn canonical_request(
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    payload_sha256: &str,
+) -> (String, String) {
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let request = format!(
+        "{}\n{}\n\n{}\n{}\n{}",
+        method, path, canonical_headers, signed_headers, payload_sha256
+    );
+    (signed_headers, request)
+}
+
+fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    [date, region, service, "aws4_request"]
+        .iter()
+        .fold(format!("AWS4{}", secret_key).into_bytes(), |key, part| {
+            hmac_sha256(&key, part.as_bytes())
+        })
+}
+
+/// Hex signature of `canonical_request` made at `amz_date` within `scope`.
+fn signature(key: &[u8], amz_date: &str, scope: &str, canonical_request: &str) -> String {
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+    hex::encode(hmac_sha256(key, string_to_sign.as_bytes()))
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn describe_error(action: &str, error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(status, response) => {
+            let body = response.into_string().unwrap_or_default();
+            format!("{} failed with HTTP {}: {}", action, status, body.trim())
+        }
+        ureq::Error::Transport(e) => format!("{} failed: {}", action, e),
+    }
+}
+
+impl BackupDestination for S3Destination<'_> {
+    fn upload(&self, local_path: &Path, name: &str) -> Result<(), String> {
+        let key = format!("{}/{}", self.config.prefix.trim_matches('/'), name);
+        let url = self.object_url(&key)?;
+
+        // Signing the real payload hash lets the store reject corrupted uploads
+        let payload_sha256 = manifest::sha256_file(local_path)
+            .map_err(|e| format!("Failed to hash backup file: {}", e))?;
+        let file =
+            File::open(local_path).map_err(|e| format!("Failed to open backup file: {}", e))?;
+        let length = file
+            .metadata()
+            .map_err(|e| format!("Failed to get file metadata: {}", e))?
+            .len();
+
+        self.request("PUT", &url, &payload_sha256)?
+            .set("Content-Length", &length.to_string())
+            .send(file)
+            .map_err(|e| describe_error(&format!("Upload of {}", name), e))?;
+        Ok(())
+    }
+
+    fn check(&self) -> Result<String, String> {
+        let url = self.object_url("")?;
+        self.request("HEAD", &url, EMPTY_SHA256)?
+            .call()
+            .map_err(|e| describe_error(&format!("Access to bucket {}", self.config.bucket), e))?;
+        Ok(format!("Bucket {} is reachable", self.config.bucket))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Credentials used by the examples in the AWS Signature Version 4 documentation.
+    const SECRET_KEY: &str = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+
+    #[test]
+    fn derives_the_documented_signing_key() {
+        let key = signing_key(SECRET_KEY, "20120215", "us-east-1", "iam");
+        assert_eq!(
+            hex::encode(key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn signs_the_get_vanilla_test_vector() {
+        let headers = [
+            ("host", "example.amazonaws.com"),
+            ("x-amz-date", "20150830T123600Z"),
+        ];
+        let (signed_headers, request) = canonical_request("GET", "/", &headers, EMPTY_SHA256);
+        assert_eq!(signed_headers, "host;x-amz-date");
+        assert_eq!(
+            hex::encode(Sha256::digest(request.as_bytes())),
+            "bb579772317eb040ac9ed261061d46c1f17a8133879d6129b6e1c25292927e63"
+        );
+
+        let key = signing_key(SECRET_KEY, "20150830", "us-east-1", "service");
+        assert_eq!(
+            signature(
+                &key,
+                "20150830T123600Z",
+                "20150830/us-east-1/service/aws4_request",
+                &request
+            ),
+            "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn addresses_objects_by_path_or_virtual_host() {
+        let mut config = S3Config {
+            endpoint: "http://minio.local:9000".to_string(),
+            region: default_region(),
+            bucket: "backups".to_string(),
+            prefix: "cardiopc/".to_string(),
+            access_key: "AKIDEXAMPLE".to_string(),
+            secret_key: None,
+            path_style: true,
+        };
+        let url = S3Destination::new(&config)
+            .object_url("cardiopc//a b.sql")
+            .unwrap();
+        assert_eq!(
+            url.as_str(),
+            "http://minio.local:9000/backups/cardiopc/a%20b.sql"
+        );
+
+        config.endpoint = "https://s3.eu-west-3.amazonaws.com".to_string();
+        config.path_style = false;
+        let url = S3Destination::new(&config).object_url("").unwrap();
+        assert_eq!(url.as_str(), "https://backups.s3.eu-west-3.amazonaws.com/");
+    }
+}
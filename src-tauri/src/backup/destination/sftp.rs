@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use ssh2::{HashType, Session};
+use std::fs::File;
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+
+use super::{BackupDestination, CONNECT_TIMEOUT, IO_TIMEOUT};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SftpConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub username: String,
+    /// This and the key passphrase are kept in the OS keyring once saved and
+    /// never sent back to the frontend.
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Path to a private key file, used instead of the password when set.
+    #[serde(default)]
+    pub private_key: Option<String>,
+    #[serde(default)]
+    pub private_key_passphrase: Option<String>,
+    /// Remote directory, which must already exist.
+    pub directory: String,
+    /// Hex SHA-256 of the server host key. Uploads are refused until it is
+    /// pinned; `test_backup_destination` reports the value to pin.
+    #[serde(default)]
+    pub host_key_sha256: Option<String>,
+}
+
+fn default_port() -> u16 {
+    22
+}
+
+pub struct SftpDestination<'a> {
+    config: &'a SftpConfig,
+}
+
+impl<'a> SftpDestination<'a> {
+    pub fn new(config: &'a SftpConfig) -> Self {
+        SftpDestination { config }
+    }
+
+    /// Connects and authenticates, returning the session and the server's host key fingerprint.
+    fn connect(&self) -> Result<(Session, String), String> {
+        let config = self.config;
+        let connect_error = |e: io::Error| {
+            format!(
+                "Failed to connect to {}:{}: {}",
+                config.host, config.port, e
+            )
+        };
+        let mut last_error = io::Error::new(io::ErrorKind::NotFound, "host not found");
+        let tcp = (config.host.as_str(), config.port)
+            .to_socket_addrs()
+            .map_err(connect_error)?
+            .find_map(|addr| {
+                TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+                    .map_err(|e| last_error = e)
+                    .ok()
+            })
+            .ok_or_else(|| connect_error(last_error))?;
+
+        let mut session =
+            Session::new().map_err(|e| format!("Failed to start SSH session: {}", e))?;
+        // Applies to every blocking libssh2 call, including the upload writes
+        session.set_timeout(IO_TIMEOUT.as_millis() as u32);
+        session.set_tcp_stream(tcp);
+        session
+            .handshake()
+            .map_err(|e| format!("SSH handshake failed: {}", e))?;
+
+        let fingerprint = session
+            .host_key_hash(HashType::Sha256)
+            .map(hex::encode)
+            .ok_or_else(|| "Server did not provide a host key".to_string())?;
+        if let Some(expected) = &config.host_key_sha256 {
+            if !expected.trim().eq_ignore_ascii_case(&fingerprint) {
+                return Err(format!(
+                    "Host key mismatch for {}: expected {}, got {}",
+                    config.host, expected, fingerprint
+                ));
+            }
+        }
+
+        let auth = match (&config.private_key, &config.password) {
+            (Some(key), _) => session.userauth_pubkey_file(
+                &config.username,
+                None,
+                Path::new(key),
+                config.private_key_passphrase.as_deref(),
+            ),
+            (None, Some(password)) => session.userauth_password(&config.username, password),
+            (None, None) => return Err("SFTP password or private key is required".to_string()),
+        };
+        auth.map_err(|e| format!("SFTP authentication failed: {}", e))?;
+
+        Ok((session, fingerprint))
+    }
+
+    fn remote_path(&self, name: &str) -> String {
+        format!("{}/{}", self.config.directory.trim_end_matches('/'), name)
+    }
+}
+
+impl BackupDestination for SftpDestination<'_> {
+    fn upload(&self, local_path: &Path, name: &str) -> Result<(), String> {
+        if self.config.host_key_sha256.is_none() {
+            return Err(format!(
+                "Host key for {} is not pinned; test the destination first",
+                self.config.host
+            ));
+        }
+
+        let (session, _) = self.connect()?;
+        let sftp = session
+            .sftp()
+            .map_err(|e| format!("Failed to open SFTP channel: {}", e))?;
+
+        let partial = self.remote_path(&format!("{}.part", name));
+        let target = self.remote_path(name);
+
+        let mut input =
+            File::open(local_path).map_err(|e| format!("Failed to open backup file: {}", e))?;
+        let mut output = sftp
+            .create(Path::new(&partial))
+            .map_err(|e| format!("Failed to create {}: {}", partial, e))?;
+        io::copy(&mut input, &mut output).map_err(|e| {
+            let _ = sftp.unlink(Path::new(&partial));
+            format!("Failed to upload {}: {}", name, e)
+        })?;
+        drop(output);
+
+        // rename does not overwrite on every server
+        let _ = sftp.unlink(Path::new(&target));
+        sftp.rename(Path::new(&partial), Path::new(&target), None)
+            .map_err(|e| format!("Failed to rename {}: {}", partial, e))
+    }
+
+    fn check(&self) -> Result<String, String> {
+        let (session, fingerprint) = self.connect()?;
+        let sftp = session
+            .sftp()
+            .map_err(|e| format!("Failed to open SFTP channel: {}", e))?;
+        let stat = sftp.stat(Path::new(&self.config.directory)).map_err(|e| {
+            format!(
+                "Remote directory {} not found: {}",
+                self.config.directory, e
+            )
+        })?;
+        if !stat.is_dir() {
+            return Err(format!("{} is not a directory", self.config.directory));
+        }
+
+        Ok(format!(
+            "Connected to {} (host key SHA-256 {})",
+            self.config.host, fingerprint
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SftpConfig {
+        SftpConfig {
+            host: "nas.local".to_string(),
+            port: default_port(),
+            username: "backup".to_string(),
+            password: Some("secret".to_string()),
+            private_key: None,
+            private_key_passphrase: None,
+            directory: "/srv/backups/".to_string(),
+            host_key_sha256: None,
+        }
+    }
+
+    #[test]
+    fn joins_remote_paths() {
+        let config = config();
+        let destination = SftpDestination::new(&config);
+        assert_eq!(
+            destination.remote_path("backup.sql"),
+            "/srv/backups/backup.sql"
+        );
+    }
+
+    #[test]
+    fn refuses_uploads_until_the_host_key_is_pinned() {
+        let config = config();
+        let error = SftpDestination::new(&config)
+            .upload(Path::new("backup.sql"), "backup.sql")
+            .unwrap_err();
+        assert!(error.contains("not pinned"), "{}", error);
+    }
+}
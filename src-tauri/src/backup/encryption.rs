@@ -0,0 +1,208 @@
+use age::secrecy::SecretString;
+use age::stream::StreamWriter;
+use age::DecryptError;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::iter;
+use std::path::Path;
+
+pub const ENCRYPTED_EXTENSION: &str = ".age";
+const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// Splits a trailing `.age` off a backup filename, returning the inner name.
+pub fn strip_extension(filename: &str) -> (&str, bool) {
+    match filename.strip_suffix(ENCRYPTED_EXTENSION) {
+        Some(inner) => (inner, true),
+        None => (filename, false),
+    }
+}
+
+pub fn validate_passphrase(passphrase: &str) -> Result<(), String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!(
+            "Backup passphrase must be at least {} characters",
+            MIN_PASSPHRASE_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Destination file of a backup, optionally wrapped in an age (scrypt + ChaCha20-Poly1305) stream.
+pub enum OutputFile {
+    Plain(BufWriter<File>),
+    Encrypted(StreamWriter<BufWriter<File>>),
+}
+
+impl OutputFile {
+    pub fn create(path: &Path, passphrase: Option<&str>) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(match passphrase {
+            None => OutputFile::Plain(file),
+            Some(passphrase) => {
+                let encryptor = age::Encryptor::with_user_passphrase(SecretString::from(
+                    passphrase.to_string(),
+                ));
+                OutputFile::Encrypted(encryptor.wrap_output(file)?)
+            }
+        })
+    }
+
+    /// Writes the final authenticated chunk; without it the archive fails to decrypt.
+    pub fn finish(self) -> io::Result<()> {
+        let mut inner = match self {
+            OutputFile::Plain(w) => w,
+            OutputFile::Encrypted(w) => w.finish()?,
+        };
+        inner.flush()
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputFile::Plain(w) => w.write(buf),
+            OutputFile::Encrypted(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(w) => w.flush(),
+            OutputFile::Encrypted(w) => w.flush(),
+        }
+    }
+}
+
+pub fn open_input(
+    path: &Path,
+    encrypted: bool,
+    passphrase: Option<&str>,
+) -> Result<Box<dyn Read + Send>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to read backup file: {}", e))?;
+    if !encrypted {
+        return Ok(Box::new(file));
+    }
+
+    let passphrase = passphrase
+        .ok_or_else(|| "This backup is encrypted; a passphrase is required".to_string())?;
+
+    let decryptor =
+        age::Decryptor::new_buffered(BufReader::new(file)).map_err(describe_decrypt_error)?;
+    if !decryptor.is_scrypt() {
+        return Err("Backup is not a passphrase-encrypted archive".to_string());
+    }
+
+    let identity = age::scrypt::Identity::new(SecretString::from(passphrase.to_string()));
+    let reader = decryptor
+        .decrypt(iter::once(&identity as &dyn age::Identity))
+        .map_err(describe_decrypt_error)?;
+    Ok(Box::new(reader))
+}
+
+fn describe_decrypt_error(error: DecryptError) -> String {
+    match error {
+        DecryptError::DecryptionFailed | DecryptError::NoMatchingKeys => {
+            "Wrong passphrase for this backup".to_string()
+        }
+        DecryptError::InvalidHeader | DecryptError::InvalidMac => {
+            "Backup file is corrupted or has been tampered with".to_string()
+        }
+        other => format!("Failed to decrypt backup: {}", other),
+    }
+}
+
+/// Maps payload errors raised while reading a decrypted stream to a readable message.
+pub fn describe_read_error(error: &io::Error) -> Option<String> {
+    (error.kind() == io::ErrorKind::InvalidData)
+        .then(|| "Backup file is corrupted or has been tampered with".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSPHRASE: &str = "correct horse battery";
+    const DUMP: &[u8] = b"-- MySQL dump 10.13\nINSERT INTO t VALUES (1);\n-- Dump completed\n";
+
+    fn encrypted_backup(dir: &Path) -> std::path::PathBuf {
+        let path = dir.join("backup.sql.age");
+        let mut output = OutputFile::create(&path, Some(PASSPHRASE)).unwrap();
+        output.write_all(DUMP).unwrap();
+        output.finish().unwrap();
+        path
+    }
+
+    fn read_all(path: &Path, passphrase: Option<&str>) -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        open_input(path, true, passphrase)?
+            .read_to_end(&mut data)
+            .map_err(|e| describe_read_error(&e).unwrap_or_else(|| e.to_string()))?;
+        Ok(data)
+    }
+
+    #[test]
+    fn round_trips_encrypted_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = encrypted_backup(dir.path());
+        assert_ne!(std::fs::read(&path).unwrap(), DUMP);
+        assert_eq!(read_all(&path, Some(PASSPHRASE)).unwrap(), DUMP);
+    }
+
+    #[test]
+    fn reports_a_wrong_or_missing_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = encrypted_backup(dir.path());
+        assert_eq!(
+            read_all(&path, Some("wrong passphrase")).unwrap_err(),
+            "Wrong passphrase for this backup"
+        );
+        assert!(read_all(&path, None)
+            .unwrap_err()
+            .contains("a passphrase is required"));
+    }
+
+    #[test]
+    fn reports_tampered_payloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = encrypted_backup(dir.path());
+        let mut data = std::fs::read(&path).unwrap();
+        *data.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, data).unwrap();
+        assert_eq!(
+            read_all(&path, Some(PASSPHRASE)).unwrap_err(),
+            "Backup file is corrupted or has been tampered with"
+        );
+    }
+
+    #[test]
+    fn reports_tampered_headers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = encrypted_backup(dir.path());
+        let mut data = std::fs::read(&path).unwrap();
+        // The header MAC line starts with "--- "
+        let mac = data.windows(4).position(|w| w == b"--- ").unwrap() + 4;
+        data[mac] = if data[mac] == b'A' { b'B' } else { b'A' };
+        std::fs::write(&path, data).unwrap();
+        assert_eq!(
+            read_all(&path, Some(PASSPHRASE)).unwrap_err(),
+            "Backup file is corrupted or has been tampered with"
+        );
+    }
+
+    #[test]
+    fn requires_passphrases_of_minimum_length() {
+        assert!(validate_passphrase("1234567").is_err());
+        assert!(validate_passphrase("12345678").is_ok());
+        // Counted in characters, not bytes
+        assert!(validate_passphrase("ééééééé").is_err());
+    }
+
+    #[test]
+    fn strips_the_encrypted_extension() {
+        assert_eq!(
+            strip_extension("backup.sql.gz.age"),
+            ("backup.sql.gz", true)
+        );
+        assert_eq!(strip_extension("backup.sql.gz"), ("backup.sql.gz", false));
+    }
+}
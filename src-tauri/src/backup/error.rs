@@ -0,0 +1,120 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+/// Error returned by the backup commands. Serialized as `{ code, message }`
+/// so the frontend can branch on `code` without parsing the message.
+#[derive(Debug, Clone)]
+pub enum BackupError {
+    /// The named backup does not exist in the backup directory.
+    NotFound(String),
+    /// The filename does not follow the backup naming scheme.
+    UnsupportedFile(String),
+    /// The passphrase was rejected.
+    Passphrase(String),
+    /// The pre-restore snapshot failed, so the restore never started.
+    SnapshotFailed(String),
+    /// The restore failed and the live database was put back from the snapshot.
+    RolledBack {
+        snapshot: String,
+        error: String,
+    },
+    /// The restore failed and so did replaying the snapshot.
+    RollbackFailed {
+        snapshot: String,
+        error: String,
+        rollback_error: String,
+    },
+    /// The backup predates the live schema; confirm with `allow_older_schema`
+    /// to restore it anyway.
+    OlderSchema {
+        backup: Option<i64>,
+        live: Option<i64>,
+    },
+    /// Another backup or restore job is running; holds its id.
+    Busy(String),
+    /// No running job has this id.
+    JobNotFound(String),
+    /// Stopped through `cancel_job`.
+    Cancelled,
+    Failed(String),
+}
+
+impl BackupError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            BackupError::NotFound(_) => "not_found",
+            BackupError::UnsupportedFile(_) => "unsupported_file",
+            BackupError::Passphrase(_) => "passphrase",
+            BackupError::SnapshotFailed(_) => "snapshot_failed",
+            BackupError::RolledBack { .. } => "rolled_back",
+            BackupError::RollbackFailed { .. } => "rollback_failed",
+            BackupError::OlderSchema { .. } => "older_schema",
+            BackupError::Busy(_) => "busy",
+            BackupError::JobNotFound(_) => "job_not_found",
+            BackupError::Cancelled => "cancelled",
+            BackupError::Failed(_) => "failed",
+        }
+    }
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackupError::NotFound(filename) => write!(f, "Backup file not found: {}", filename),
+            BackupError::UnsupportedFile(filename) => {
+                write!(f, "Unsupported backup file type: {}", filename)
+            }
+            BackupError::Passphrase(message) | BackupError::Failed(message) => f.write_str(message),
+            BackupError::SnapshotFailed(error) => {
+                write!(f, "Restore aborted, pre-restore snapshot failed: {}", error)
+            }
+            BackupError::RolledBack { snapshot, error } => write!(
+                f,
+                "Restore failed and the database was rolled back to {}: {}",
+                snapshot, error
+            ),
+            BackupError::RollbackFailed {
+                snapshot,
+                error,
+                rollback_error,
+            } => write!(
+                f,
+                "Restore failed: {}. Rolling back to {} also failed: {}",
+                error, snapshot, rollback_error
+            ),
+            BackupError::OlderSchema { backup, live } => write!(
+                f,
+                "Backup was taken at schema version {} but the database is at {}; \
+                 restoring it drops the tables added since",
+                backup.map_or("unknown".to_string(), |v| v.to_string()),
+                live.map_or("unknown".to_string(), |v| v.to_string())
+            ),
+            BackupError::Busy(job_id) => write!(
+                f,
+                "Another backup or restore is already running (job {})",
+                job_id
+            ),
+            BackupError::JobNotFound(job_id) => write!(f, "No running job with id {}", job_id),
+            BackupError::Cancelled => f.write_str("Cancelled by user"),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+/// Lets the `Result<_, String>` helpers be used with `?` in the commands.
+impl From<String> for BackupError {
+    fn from(message: String) -> Self {
+        BackupError::Failed(message)
+    }
+}
+
+impl Serialize for BackupError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("BackupError", 2)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
@@ -0,0 +1,168 @@
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use tauri::{command, AppHandle, State};
+
+use super::{location, parse_backup_name, BackupError, BackupInfo, BackupType};
+use crate::db_config::{self, DbConfig, DbConfigState};
+
+const DEFAULT_HISTORY_LIMIT: u32 = 50;
+
+/// Appends a row to `backup_history`. `error` marks the run as failed.
+pub async fn record(
+    config: &DbConfig,
+    backup_type: BackupType,
+    filename: &str,
+    size_bytes: Option<u64>,
+    error: Option<&str>,
+) -> Result<(), String> {
+    let mut conn = db_config::connect(config).await?;
+    let size_mb = size_bytes.map(|size| (size as f64 / (1024.0 * 1024.0) * 100.0).round() / 100.0);
+    let status = if error.is_none() { "Success" } else { "Failed" };
+
+    sqlx::query(
+        "INSERT INTO backup_history (type, filename, size_mb, status, error_message)
+         VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(backup_type.as_str())
+    .bind(filename)
+    .bind(size_mb)
+    .bind(status)
+    .bind(error)
+    .execute(&mut conn)
+    .await
+    .map_err(|e| format!("Failed to record backup history: {}", e))?;
+
+    Ok(())
+}
+
+/// Like `record`, but a history failure is only logged so it never masks the
+/// outcome of the backup or restore itself.
+pub async fn record_logged(
+    config: &DbConfig,
+    backup_type: BackupType,
+    filename: &str,
+    size_bytes: Option<u64>,
+    error: Option<&str>,
+) {
+    if let Err(e) = record(config, backup_type, filename, size_bytes, error).await {
+        log::warn!("{}", e);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryEntry {
+    pub id: i64,
+    #[serde(rename = "type")]
+    pub backup_type: String,
+    pub filename: String,
+    pub size_mb: Option<f64>,
+    pub status: String,
+    pub error_message: Option<String>,
+    pub timestamp: String,
+    /// Whether the file named by the row is still in the backup directory.
+    pub file_exists: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackupHistory {
+    pub entries: Vec<HistoryEntry>,
+    /// Backup files on disk with no history row at all, e.g. copied in by
+    /// hand or made before the backend recorded its own runs.
+    pub untracked: Vec<BackupInfo>,
+    /// Untracked files that could not be read.
+    pub unreadable: Vec<UnreadableBackup>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnreadableBackup {
+    pub filename: String,
+    pub error: String,
+}
+
+type HistoryRow = (
+    i64,
+    String,
+    String,
+    Option<String>,
+    String,
+    Option<String>,
+    String,
+);
+
+/// Latest `backup_history` rows checked against the backup directory.
+#[command]
+pub async fn get_backup_history(
+    app: AppHandle,
+    db: State<'_, DbConfigState>,
+    limit: Option<u32>,
+) -> Result<BackupHistory, BackupError> {
+    let config = db.current()?;
+    let backup_dir = location::backup_dir(&app)?;
+
+    let on_disk: Vec<String> = fs::read_dir(&backup_dir)
+        .map_err(|e| format!("Failed to read backup directory: {}", e))?
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+        .filter(|filename| parse_backup_name(filename).is_some())
+        .collect();
+
+    let mut conn = db_config::connect(&config).await?;
+    // DECIMAL and TIMESTAMP need extra sqlx features, so they come back as text
+    let rows: Vec<HistoryRow> = sqlx::query_as(
+        "SELECT CAST(id AS SIGNED), CAST(type AS CHAR), filename, CAST(size_mb AS CHAR),
+                CAST(status AS CHAR), error_message, CAST(timestamp AS CHAR)
+         FROM backup_history ORDER BY timestamp DESC, id DESC LIMIT ?",
+    )
+    .bind(limit.unwrap_or(DEFAULT_HISTORY_LIMIT))
+    .fetch_all(&mut conn)
+    .await
+    .map_err(|e| format!("Failed to read backup history: {}", e))?;
+
+    // Checked against every row, not only the ones returned
+    let recorded: HashSet<String> =
+        sqlx::query_scalar("SELECT DISTINCT filename FROM backup_history")
+            .fetch_all(&mut conn)
+            .await
+            .map_err(|e| format!("Failed to read backup history: {}", e))?
+            .into_iter()
+            .collect();
+
+    let entries = rows
+        .into_iter()
+        .map(
+            |(id, backup_type, filename, size_mb, status, error_message, timestamp)| {
+                let file_exists = on_disk.contains(&filename);
+                HistoryEntry {
+                    id,
+                    backup_type,
+                    size_mb: size_mb.and_then(|size| size.parse().ok()),
+                    filename,
+                    status,
+                    error_message,
+                    timestamp,
+                    file_exists,
+                }
+            },
+        )
+        .collect();
+
+    let mut untracked = Vec::new();
+    let mut unreadable = Vec::new();
+    for filename in on_disk.iter().filter(|f| !recorded.contains(*f)) {
+        match BackupInfo::read(&backup_dir.join(filename), filename) {
+            Ok(info) => untracked.push(info),
+            Err(e) => unreadable.push(UnreadableBackup {
+                filename: filename.clone(),
+                error: e.to_string(),
+            }),
+        }
+    }
+    untracked.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+
+    Ok(BackupHistory {
+        entries,
+        untracked,
+        unreadable,
+    })
+}
@@ -0,0 +1,268 @@
+use chrono::Local;
+use serde::Serialize;
+use std::io;
+use std::process::{Child, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tauri::{command, AppHandle, Emitter, Manager, State};
+
+use super::{
+    create_backup, BackupEngine, BackupError, BackupFormat, BackupParams, BackupType, Compression,
+};
+use crate::db_config::DbConfigState;
+
+/// Emitted once when a job started with `start_*_job` finishes.
+const JOB_EVENT: &str = "backup-job";
+const CANCELLED: &str = "Cancelled by user";
+const CHILD_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    Backup,
+    Restore,
+}
+
+#[derive(Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    /// mysqldump/mysql child of the running job, killed on cancel.
+    child: Mutex<Option<Arc<Mutex<Child>>>>,
+}
+
+/// Handed down to the dump and restore code so it can stop early. The
+/// default token belongs to no job and is never cancelled.
+#[derive(Clone, Default)]
+pub struct JobToken {
+    id: Option<String>,
+    state: Arc<CancelState>,
+}
+
+impl JobToken {
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Fails once the job has been cancelled; called between tables and statements.
+    pub fn check(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            return Err(CANCELLED.to_string());
+        }
+        Ok(())
+    }
+
+    fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::SeqCst);
+        if let Ok(slot) = self.state.child.lock() {
+            if let Some(child) = slot.as_ref() {
+                if let Ok(mut child) = child.lock() {
+                    let _ = child.kill();
+                }
+            }
+        }
+    }
+
+    /// Registers a spawned client process so that cancelling kills it. Its
+    /// pipes must already have been taken.
+    pub fn attach(&self, child: Child) -> AttachedChild<'_> {
+        let child = Arc::new(Mutex::new(child));
+        if let Ok(mut slot) = self.state.child.lock() {
+            *slot = Some(child.clone());
+        }
+        // Cancelled between spawning and attaching
+        if self.is_cancelled() {
+            if let Ok(mut child) = child.lock() {
+                let _ = child.kill();
+            }
+        }
+        AttachedChild { token: self, child }
+    }
+}
+
+/// A client process registered with a job; detached again when dropped.
+pub struct AttachedChild<'a> {
+    token: &'a JobToken,
+    child: Arc<Mutex<Child>>,
+}
+
+impl AttachedChild<'_> {
+    /// Waits for the process to exit. Polls so `cancel_job` can take the lock
+    /// and kill it in the meantime.
+    pub fn wait(&self) -> io::Result<ExitStatus> {
+        loop {
+            let status = self
+                .child
+                .lock()
+                .map_err(|_| io::Error::other("Child process lock poisoned"))?
+                .try_wait()?;
+            if let Some(status) = status {
+                return Ok(status);
+            }
+            thread::sleep(CHILD_POLL_INTERVAL);
+        }
+    }
+}
+
+impl Drop for AttachedChild<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut slot) = self.token.state.child.lock() {
+            *slot = None;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobInfo {
+    pub job_id: String,
+    pub kind: JobKind,
+}
+
+struct ActiveJob {
+    info: JobInfo,
+    token: JobToken,
+}
+
+/// Allows one backup or restore at a time across commands and the scheduler.
+#[derive(Default)]
+pub struct JobManager(Arc<Mutex<Option<ActiveJob>>>);
+
+impl JobManager {
+    /// Claims the job slot, failing if another job holds it.
+    pub fn start(&self, kind: JobKind) -> Result<Job, BackupError> {
+        let mut active = self.0.lock().map_err(|_| "Job lock poisoned".to_string())?;
+        if let Some(running) = active.as_ref() {
+            return Err(BackupError::Busy(running.info.job_id.clone()));
+        }
+
+        let info = JobInfo {
+            job_id: format!("{}", Local::now().timestamp_micros()),
+            kind,
+        };
+        let token = JobToken {
+            id: Some(info.job_id.clone()),
+            state: Arc::default(),
+        };
+        *active = Some(ActiveJob {
+            info: info.clone(),
+            token: token.clone(),
+        });
+
+        Ok(Job {
+            info,
+            token,
+            slot: self.0.clone(),
+        })
+    }
+}
+
+/// Holds the job slot until dropped.
+pub struct Job {
+    pub info: JobInfo,
+    pub token: JobToken,
+    slot: Arc<Mutex<Option<ActiveJob>>>,
+}
+
+impl Job {
+    /// Reports an error caused by `cancel_job` as such rather than as
+    /// whatever the killed process printed. A rolled back restore keeps its
+    /// own error so the caller learns about the rollback.
+    pub fn finish<T>(&self, result: Result<T, BackupError>) -> Result<T, BackupError> {
+        match result {
+            Err(BackupError::Failed(_) | BackupError::SnapshotFailed(_))
+                if self.token.is_cancelled() =>
+            {
+                Err(BackupError::Cancelled)
+            }
+            result => result,
+        }
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        if let Ok(mut active) = self.slot.lock() {
+            if active
+                .as_ref()
+                .is_some_and(|a| a.info.job_id == self.info.job_id)
+            {
+                *active = None;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct JobFinished {
+    job_id: String,
+    kind: JobKind,
+    /// The command's return value on success.
+    result: Option<serde_json::Value>,
+    error: Option<BackupError>,
+}
+
+fn emit_finished<T: Serialize>(app: &AppHandle, job: &Job, result: Result<T, BackupError>) {
+    let (result, error) = match result {
+        Ok(value) => (serde_json::to_value(value).ok(), None),
+        Err(e) => (None, Some(e)),
+    };
+    let _ = app.emit(
+        JOB_EVENT,
+        JobFinished {
+            job_id: job.info.job_id.clone(),
+            kind: job.info.kind,
+            result,
+            error,
+        },
+    );
+}
+
+/// Like `create_database_backup`, but returns a job id straight away. Progress
+/// arrives as `backup-progress` and the outcome as a `backup-job` event.
+#[command]
+pub fn start_backup_job(
+    app: AppHandle,
+    db: State<'_, DbConfigState>,
+    backup_type: BackupType,
+    format: Option<BackupFormat>,
+    compression: Option<Compression>,
+    passphrase: Option<String>,
+    engine: Option<BackupEngine>,
+) -> Result<JobInfo, BackupError> {
+    let config = db.current()?;
+    let params = BackupParams::new(backup_type, format, compression, passphrase, engine)?;
+    let job = app.state::<JobManager>().start(JobKind::Backup)?;
+    let info = job.info.clone();
+
+    tauri::async_runtime::spawn(async move {
+        let result = create_backup(&app, config, params, job.token.clone()).await;
+        emit_finished(&app, &job, job.finish(result));
+    });
+    Ok(info)
+}
+
+/// The running backup or restore, if any.
+#[command]
+pub fn get_current_job(jobs: State<'_, JobManager>) -> Result<Option<JobInfo>, BackupError> {
+    let active = jobs.0.lock().map_err(|_| "Job lock poisoned".to_string())?;
+    Ok(active.as_ref().map(|job| job.info.clone()))
+}
+
+/// Stops a running job, killing its mysqldump/mysql process. A restore that
+/// already started is rolled back from its pre-restore snapshot.
+#[command]
+pub fn cancel_job(jobs: State<'_, JobManager>, job_id: String) -> Result<(), BackupError> {
+    let active = jobs.0.lock().map_err(|_| "Job lock poisoned".to_string())?;
+    match active.as_ref() {
+        Some(job) if job.info.job_id == job_id => {
+            job.token.cancel();
+            Ok(())
+        }
+        _ => Err(BackupError::JobNotFound(job_id)),
+    }
+}
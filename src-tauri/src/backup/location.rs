@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{command, AppHandle, Manager};
+
+use super::{manifest, parse_backup_name, BackupError};
+use crate::config_file;
+
+const LOCATION_FILE: &str = "backup_location.json";
+/// Folder used by versions that wrote backups relative to the working directory.
+const LEGACY_DIR: &str = "backups";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BackupLocation {
+    directory: Option<PathBuf>,
+}
+
+fn default_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app directory: {}", e))?;
+    Ok(app_dir.join("backups"))
+}
+
+fn configured_dir(app: &AppHandle) -> Option<PathBuf> {
+    config_file::load::<BackupLocation>(app, LOCATION_FILE)
+        .ok()??
+        .directory
+}
+
+/// Directory holding backups: the user-chosen folder if any, otherwise
+/// `backups/` under the app data dir. Created on first use.
+pub fn backup_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = match configured_dir(app) {
+        Some(dir) => dir,
+        None => default_dir(app)?,
+    };
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create backup directory: {}", e))?;
+    Ok(dir)
+}
+
+fn move_file(source: &Path, target: &Path) -> Result<(), String> {
+    // rename fails across volumes, e.g. when moving to a network share
+    if fs::rename(source, target).is_err() {
+        fs::copy(source, target)
+            .and_then(|_| fs::remove_file(source))
+            .map_err(|e| format!("Failed to move {}: {}", source.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Moves the backups in `from` into `to`, each with its manifest, skipping
+/// names that already exist at the destination. Other files are left alone,
+/// since the folder may be shared with unrelated data.
+fn move_files(from: &Path, to: &Path) -> Result<usize, String> {
+    let entries =
+        fs::read_dir(from).map_err(|e| format!("Failed to read backup directory: {}", e))?;
+
+    let mut moved = 0;
+    for entry in entries.flatten() {
+        let source = entry.path();
+        let is_backup = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| parse_backup_name(name).is_some());
+        if !is_backup || !source.is_file() {
+            continue;
+        }
+        let target = to.join(entry.file_name());
+        if target.exists() {
+            continue;
+        }
+        move_file(&source, &target)?;
+        moved += 1;
+
+        let source_manifest = manifest::manifest_path(&source);
+        let target_manifest = manifest::manifest_path(&target);
+        if source_manifest.is_file() && !target_manifest.exists() {
+            move_file(&source_manifest, &target_manifest)?;
+        }
+    }
+    Ok(moved)
+}
+
+/// One-time move of backups left in `./backups` by earlier versions.
+pub fn migrate_legacy_dir(app: &AppHandle) {
+    let legacy = PathBuf::from(LEGACY_DIR);
+    if !legacy.is_dir() {
+        return;
+    }
+
+    let result = backup_dir(app).and_then(|target| {
+        let same_dir = match (legacy.canonicalize(), target.canonicalize()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        };
+        if same_dir {
+            return Ok(0);
+        }
+        move_files(&legacy, &target)
+    });
+
+    match result {
+        Ok(0) => {}
+        Ok(moved) => {
+            // Only succeeds once the folder is empty; leftovers stay where they were
+            let _ = fs::remove_dir(&legacy);
+            log::info!(
+                "Moved {} legacy backup file(s) to the app data directory",
+                moved
+            );
+        }
+        Err(e) => log::warn!("Failed to migrate legacy backups: {}", e),
+    }
+}
+
+#[command]
+pub fn get_backup_directory(app: AppHandle) -> Result<String, BackupError> {
+    Ok(backup_dir(&app)?.to_string_lossy().into_owned())
+}
+
+/// Changes where backups are written. `None` restores the default location.
+#[command]
+pub fn set_backup_directory(
+    app: AppHandle,
+    directory: Option<String>,
+    move_existing: Option<bool>,
+) -> Result<String, BackupError> {
+    let previous = backup_dir(&app)?;
+
+    let directory = directory
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from);
+    if let Some(dir) = &directory {
+        if !dir.is_absolute() {
+            return Err(format!(
+                "Backup directory must be an absolute path: {}",
+                dir.display()
+            )
+            .into());
+        }
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create backup directory: {}", e))?;
+        // Fail now rather than at the next scheduled backup
+        tempfile::tempfile_in(dir)
+            .map_err(|e| format!("Backup directory is not writable: {}", e))?;
+    }
+
+    config_file::save(&app, LOCATION_FILE, &BackupLocation { directory })?;
+
+    let current = backup_dir(&app)?;
+    if move_existing.unwrap_or(false) && previous != current {
+        move_files(&previous, &current)?;
+    }
+
+    Ok(current.to_string_lossy().into_owned())
+}
@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::mysql::MySqlConnection;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+
+use super::compression::Compression;
+use super::native::DUMP_HEADER;
+use super::{BackupFormat, BackupType};
+
+pub const MANIFEST_SUFFIX: &str = ".manifest.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub filename: String,
+    pub sha256: String,
+    pub size_bytes: u64,
+    /// Size of the dump or archive before compression and encryption. Absent from older manifests.
+    #[serde(default)]
+    pub uncompressed_size_bytes: Option<u64>,
+    pub app_version: String,
+    pub migration_version: Option<i64>,
+    pub tables: BTreeMap<String, i64>,
+    #[serde(default)]
+    pub format: BackupFormat,
+    /// Medical files bundled in a full backup.
+    #[serde(default)]
+    pub attachments: Vec<String>,
+    /// Manual, Automatic or Pre-restore. Absent from older manifests.
+    #[serde(default)]
+    pub backup_type: Option<BackupType>,
+    pub compression: Compression,
+    pub encrypted: bool,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VerificationReport {
+    pub filename: String,
+    pub valid: bool,
+    pub manifest_found: bool,
+    pub checksum_ok: Option<bool>,
+    pub header_ok: Option<bool>,
+    pub complete: Option<bool>,
+    pub errors: Vec<String>,
+}
+
+/// Snapshot of the database that a dump is expected to contain.
+pub struct DatabaseSummary {
+    pub migration_version: Option<i64>,
+    pub tables: BTreeMap<String, i64>,
+}
+
+pub fn manifest_path(backup_path: &Path) -> PathBuf {
+    let mut name = backup_path.file_name().unwrap_or_default().to_os_string();
+    name.push(MANIFEST_SUFFIX);
+    backup_path.with_file_name(name)
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+pub fn write(backup_path: &Path, manifest: &BackupManifest) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(manifest)
+        .map_err(|e| format!("Failed to serialize backup manifest: {}", e))?;
+    fs::write(manifest_path(backup_path), content)
+        .map_err(|e| format!("Failed to write backup manifest: {}", e))
+}
+
+pub fn read(backup_path: &Path) -> Result<Option<BackupManifest>, String> {
+    let path = manifest_path(backup_path);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read backup manifest: {}", e))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("Invalid backup manifest: {}", e))
+}
+
+pub fn quote_identifier(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+pub async fn list_tables(conn: &mut MySqlConnection) -> Result<Vec<String>, String> {
+    sqlx::query_scalar(
+        "SELECT CAST(table_name AS CHAR) FROM information_schema.tables
+         WHERE table_schema = DATABASE() AND table_type = 'BASE TABLE'
+         ORDER BY table_name",
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to list tables: {}", e))
+}
+
+/// Latest applied migration; `None` when the schema predates migrations.
+pub async fn migration_version(conn: &mut MySqlConnection) -> Option<i64> {
+    // The SQL plugin migrates through sqlx, which tracks versions in _sqlx_migrations
+    sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1")
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap_or(None)
+}
+
+/// Drops `tables` regardless of the foreign keys pointing at them.
+pub async fn drop_tables(conn: &mut MySqlConnection, tables: &[String]) -> Result<(), String> {
+    if tables.is_empty() {
+        return Ok(());
+    }
+    let names: Vec<String> = tables.iter().map(|t| quote_identifier(t)).collect();
+    sqlx::raw_sql(&format!(
+        "SET FOREIGN_KEY_CHECKS=0; DROP TABLE IF EXISTS {}; SET FOREIGN_KEY_CHECKS=1",
+        names.join(", ")
+    ))
+    .execute(&mut *conn)
+    .await
+    .map(|_| ())
+    .map_err(|e| format!("Failed to drop tables: {}", e))
+}
+
+pub async fn summarize_database(conn: &mut MySqlConnection) -> Result<DatabaseSummary, String> {
+    let migration_version = migration_version(&mut *conn).await;
+
+    let mut tables = BTreeMap::new();
+    for table in list_tables(&mut *conn).await? {
+        let count: i64 = sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM {}",
+            quote_identifier(&table)
+        ))
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to count rows in {}: {}", table, e))?;
+        tables.insert(table, count);
+    }
+
+    Ok(DatabaseSummary {
+        migration_version,
+        tables,
+    })
+}
+
+/// Reads a decoded dump to the end, checking the mysqldump header and the
+/// "Dump completed" trailer that is only written when the dump finished.
+pub fn check_dump<R: BufRead>(reader: &mut R) -> io::Result<(bool, bool)> {
+    let mut line = Vec::new();
+    let mut first_line: Option<Vec<u8>> = None;
+    let mut last_line = Vec::new();
+
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if first_line.is_none() {
+            first_line = Some(line.clone());
+        }
+        if !line.iter().all(u8::is_ascii_whitespace) {
+            last_line.clear();
+            last_line.extend_from_slice(&line);
+        }
+    }
+
+    let header_ok = first_line
+        .map(|l| {
+            l.starts_with(b"-- MySQL dump")
+                || l.starts_with(b"-- MariaDB dump")
+                || l.starts_with(DUMP_HEADER.as_bytes())
+        })
+        .unwrap_or(false);
+    let complete = last_line.starts_with(b"-- Dump completed");
+    Ok((header_ok, complete))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::verify_file;
+
+    const COMPLETE_DUMP: &str = "-- MySQL dump 10.13  Distrib 8.0.36\n\
+                                 --\n\
+                                 DROP TABLE IF EXISTS `t`;\n\
+                                 INSERT INTO `t` VALUES (1);\n\
+                                 \n\
+                                 -- Dump completed on 2024-06-15 12:00:00\n\
+                                 \n";
+
+    fn check(dump: &str) -> (bool, bool) {
+        check_dump(&mut dump.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn accepts_complete_dumps() {
+        assert_eq!(check(COMPLETE_DUMP), (true, true));
+        assert_eq!(
+            check(&format!(
+                "{} 1.0.0\nSELECT 1;\n-- Dump completed on 2024-06-15 12:00:00\n",
+                DUMP_HEADER
+            )),
+            (true, true)
+        );
+    }
+
+    #[test]
+    fn detects_dumps_missing_the_trailer() {
+        let truncated = COMPLETE_DUMP.replace("-- Dump completed on 2024-06-15 12:00:00\n", "");
+        assert_eq!(check(&truncated), (true, false));
+        assert_eq!(check(""), (false, false));
+    }
+
+    #[test]
+    fn detects_files_that_are_not_dumps() {
+        assert_eq!(
+            check("PK\u{3}\u{4}not a dump\n-- Dump completed on 2024-06-15\n"),
+            (false, true)
+        );
+    }
+
+    #[test]
+    fn writes_backup_types_as_stored_in_history() {
+        for backup_type in [
+            BackupType::Manual,
+            BackupType::Automatic,
+            BackupType::PreRestore,
+            BackupType::Rollback,
+            BackupType::Restore,
+        ] {
+            let json = serde_json::to_value(backup_type).unwrap();
+            assert_eq!(json, backup_type.as_str());
+            assert_eq!(
+                serde_json::from_value::<BackupType>(json).unwrap(),
+                backup_type
+            );
+        }
+    }
+
+    #[test]
+    fn reports_checksum_mismatches() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("backup.sql");
+        fs::write(&path, COMPLETE_DUMP).unwrap();
+        let manifest = BackupManifest {
+            filename: "backup.sql".to_string(),
+            sha256: sha256_file(&path).unwrap(),
+            size_bytes: COMPLETE_DUMP.len() as u64,
+            uncompressed_size_bytes: None,
+            app_version: "1.0.0".to_string(),
+            migration_version: None,
+            tables: BTreeMap::new(),
+            format: BackupFormat::Sql,
+            attachments: Vec::new(),
+            backup_type: None,
+            compression: Compression::None,
+            encrypted: false,
+            created_at: "2024-06-15T12:00:00Z".to_string(),
+        };
+        write(&path, &manifest).unwrap();
+
+        let report = verify_file("backup.sql", &path, None);
+        assert!(report.valid, "{:?}", report.errors);
+        assert_eq!(report.checksum_ok, Some(true));
+
+        fs::write(&path, COMPLETE_DUMP.replace("(1)", "(2)")).unwrap();
+        let report = verify_file("backup.sql", &path, None);
+        assert!(!report.valid);
+        assert_eq!(report.checksum_ok, Some(false));
+        assert_eq!(report.complete, Some(true));
+        assert_eq!(report.errors, ["Checksum does not match the manifest"]);
+    }
+}
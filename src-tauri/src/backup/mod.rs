@@ -0,0 +1,991 @@
+mod archive;
+mod client;
+mod compression;
+pub mod destination;
+mod encryption;
+mod error;
+pub mod history;
+pub mod job;
+pub mod location;
+mod manifest;
+mod native;
+pub mod patient;
+pub mod preview;
+pub mod retention;
+pub mod scheduler;
+mod scratch;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use tauri::{command, AppHandle, Emitter, Manager, State};
+
+use crate::db_config::{DbConfig, DbConfigState};
+use crate::safe_path;
+use compression::{BackupWriter, Compression};
+pub use error::BackupError;
+use job::{JobKind, JobManager, JobToken};
+use manifest::{BackupManifest, VerificationReport};
+
+const PROGRESS_EVENT: &str = "backup-progress";
+const PROGRESS_INTERVAL_BYTES: u64 = 1024 * 1024;
+const TABLE_MARKER: &[u8] = b"-- Table structure for table `";
+
+#[derive(Clone, Serialize)]
+struct BackupProgress {
+    job_id: Option<String>,
+    operation: &'static str,
+    filename: String,
+    bytes: u64,
+    total_bytes: Option<u64>,
+    tables_done: u32,
+    current_table: Option<String>,
+}
+
+/// Which program produces and replays dumps. `Auto` prefers the MySQL client
+/// tools and falls back to the built-in engine when they are not installed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupEngine {
+    #[default]
+    Auto,
+    Native,
+    Client,
+}
+
+impl BackupEngine {
+    fn use_native(self, binary: &str) -> bool {
+        match self {
+            BackupEngine::Auto => !client::is_available(binary),
+            BackupEngine::Native => true,
+            BackupEngine::Client => false,
+        }
+    }
+}
+
+/// `Sql` is a bare dump; `Full` is a tar bundling the dump with the medical
+/// attachments it references.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupFormat {
+    #[default]
+    Sql,
+    Full,
+}
+
+impl BackupFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            BackupFormat::Sql => ".sql",
+            BackupFormat::Full => ".tar",
+        }
+    }
+}
+
+/// Why a backup or restore ran, as stored in `backup_history.type` and in manifests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackupType {
+    Manual,
+    Automatic,
+    /// Safety snapshot taken before a restore.
+    #[serde(rename = "Pre-restore")]
+    PreRestore,
+    /// The snapshot replayed after a failed restore.
+    Rollback,
+    Restore,
+}
+
+impl BackupType {
+    /// Value of the `backup_history.type` ENUM.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BackupType::Manual => "Manual",
+            BackupType::Automatic => "Automatic",
+            BackupType::PreRestore => "Pre-restore",
+            BackupType::Rollback => "Rollback",
+            BackupType::Restore => "Restore",
+        }
+    }
+}
+
+/// What a backup filename says about its contents, outermost layer last:
+/// `name.sql|.tar` then `.gz|.zst` then `.age`.
+struct BackupName {
+    format: BackupFormat,
+    compression: Compression,
+    encrypted: bool,
+}
+
+fn parse_backup_name(filename: &str) -> Option<BackupName> {
+    let (inner_name, encrypted) = encryption::strip_extension(filename);
+    let (inner_name, compression) = Compression::strip_extension(inner_name);
+    let format = [BackupFormat::Sql, BackupFormat::Full]
+        .into_iter()
+        .find(|f| inner_name.ends_with(f.extension()))?;
+    Some(BackupName {
+        format,
+        compression,
+        encrypted,
+    })
+}
+
+fn table_marker(line: &[u8]) -> Option<String> {
+    let rest = line.strip_prefix(TABLE_MARKER)?;
+    let end = rest.iter().position(|&b| b == b'`')?;
+    Some(String::from_utf8_lossy(&rest[..end]).into_owned())
+}
+
+fn emit_progress<'a>(
+    app: &'a AppHandle,
+    operation: &'static str,
+    filename: &str,
+    total_bytes: Option<u64>,
+    job: &JobToken,
+) -> impl FnMut(u64, u32, Option<String>) + 'a {
+    let filename = filename.to_string();
+    let job_id = job.id().map(str::to_string);
+    move |bytes, tables_done, current_table| {
+        let _ = app.emit(
+            PROGRESS_EVENT,
+            BackupProgress {
+                job_id: job_id.clone(),
+                operation,
+                filename: filename.clone(),
+                bytes,
+                total_bytes,
+                tables_done,
+                current_table,
+            },
+        );
+    }
+}
+
+/// A backup file as reported to the frontend.
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupInfo {
+    pub filename: String,
+    pub size_bytes: u64,
+    /// RFC 3339. Taken from the manifest, else the filename or modification time.
+    pub created_at: Option<String>,
+    /// Manual, Automatic or Pre-restore; unknown for backups without a manifest.
+    #[serde(rename = "type")]
+    pub backup_type: Option<BackupType>,
+    pub format: BackupFormat,
+    pub compression: Compression,
+    pub encrypted: bool,
+    /// SHA-256 recorded in the manifest.
+    pub checksum: Option<String>,
+    /// Size of the dump or archive before compression. Recorded in the manifest; unknown
+    /// for encrypted backups without one.
+    pub uncompressed_size_bytes: Option<u64>,
+}
+
+impl BackupInfo {
+    /// Describes a backup on disk from its name, metadata and manifest.
+    fn read(backup_path: &Path, filename: &str) -> Result<Self, BackupError> {
+        let name = parse_backup_name(filename)
+            .ok_or_else(|| BackupError::UnsupportedFile(filename.to_string()))?;
+        let metadata =
+            fs::metadata(backup_path).map_err(|e| format!("Failed to get file metadata: {}", e))?;
+        // A broken manifest only costs the checksum, the file is still listed
+        let manifest = manifest::read(backup_path).ok().flatten();
+
+        // Measuring means decoding zstd streams, so only backups whose manifest lacks
+        // the size pay for it. Encrypted ones cannot be inspected at all.
+        let uncompressed_size_bytes = manifest
+            .as_ref()
+            .and_then(|m| m.uncompressed_size_bytes)
+            .or_else(|| {
+                (!name.encrypted)
+                    .then(|| compression::uncompressed_size(backup_path, name.compression).ok())
+                    .flatten()
+            });
+        let created_at = match &manifest {
+            Some(manifest) => Some(manifest.created_at.clone()),
+            None => retention::backup_time(filename, backup_path).map(|t| t.to_rfc3339()),
+        };
+
+        Ok(BackupInfo {
+            filename: filename.to_string(),
+            size_bytes: metadata.len(),
+            created_at,
+            backup_type: manifest.as_ref().and_then(|m| m.backup_type),
+            format: name.format,
+            compression: name.compression,
+            encrypted: name.encrypted,
+            checksum: manifest.map(|m| m.sha256),
+            uncompressed_size_bytes,
+        })
+    }
+}
+
+/// Result of a successful dump, shared by the command and the scheduler.
+#[derive(Debug, Serialize)]
+pub struct BackupOutcome {
+    #[serde(flatten)]
+    pub info: BackupInfo,
+    pub path: PathBuf,
+    /// Medical files bundled in a full backup.
+    pub attachments: Vec<String>,
+    /// Medical files referenced by the database but missing on disk.
+    pub missing_attachments: Vec<String>,
+    /// The bundled medical files stay encrypted with the installation key, so
+    /// restoring them elsewhere needs the key from `export_medical_files_key`.
+    pub requires_files_key: bool,
+    /// Backups deleted by the retention policy afterwards.
+    pub pruned: Vec<String>,
+    pub uploads: Vec<destination::UploadResult>,
+}
+
+fn backup_filename(
+    database: &str,
+    format: BackupFormat,
+    compression: Compression,
+    encrypted: bool,
+) -> String {
+    let timestamp = Local::now().format("%Y%m%d_%H%M%S");
+    format!(
+        "{}_backup_{}{}{}{}",
+        database,
+        timestamp,
+        format.extension(),
+        compression.extension(),
+        if encrypted {
+            encryption::ENCRYPTED_EXTENSION
+        } else {
+            ""
+        }
+    )
+}
+
+/// What to back up and how, shared by the command, the scheduler and restore snapshots.
+struct BackupRequest {
+    filename: String,
+    /// Recorded in the manifest: Manual, Automatic or Pre-restore.
+    backup_type: BackupType,
+    format: BackupFormat,
+    compression: Compression,
+    passphrase: Option<String>,
+    engine: BackupEngine,
+    /// Apply the retention policy afterwards. Off for pre-restore snapshots,
+    /// which must not prune the backup about to be restored.
+    prune: bool,
+    job: JobToken,
+}
+
+async fn perform_backup(
+    app: AppHandle,
+    config: DbConfig,
+    request: BackupRequest,
+) -> Result<BackupOutcome, String> {
+    let BackupRequest {
+        filename,
+        backup_type,
+        format,
+        compression,
+        passphrase,
+        engine,
+        prune,
+        job,
+    } = request;
+    let encrypted = passphrase.is_some();
+
+    let backup_dir = location::backup_dir(&app)?;
+    let backup_path = backup_dir.join(&filename);
+
+    let summary = {
+        let mut conn = crate::db_config::connect(&config).await?;
+        manifest::summarize_database(&mut conn).await?
+    };
+
+    let attachments = match format {
+        BackupFormat::Sql => Vec::new(),
+        BackupFormat::Full => archive::referenced_files(&config).await?,
+    };
+
+    let (uncompressed_size, missing_attachments, checksum) = {
+        let app = app.clone();
+        let filename = filename.clone();
+        let backup_path = backup_path.clone();
+        let attachments = attachments.clone();
+        let job = job.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            let options = DumpOptions {
+                format,
+                compression,
+                passphrase: passphrase.as_deref(),
+                engine,
+                job: &job,
+            };
+            let result = dump_to_file(
+                &app,
+                &config,
+                &filename,
+                &backup_path,
+                &attachments,
+                &options,
+            )
+            .and_then(|(size, missing)| {
+                manifest::sha256_file(&backup_path)
+                    .map(|checksum| (size, missing, checksum))
+                    .map_err(|e| format!("Failed to hash backup file: {}", e))
+            });
+            if result.is_err() {
+                let _ = fs::remove_file(&backup_path);
+            }
+            result
+        })
+        .await
+        .map_err(|e| format!("Backup task failed: {}", e))??
+    };
+    let attachments: Vec<String> = attachments
+        .into_iter()
+        .filter(|file| !missing_attachments.contains(file))
+        .collect();
+
+    let metadata =
+        fs::metadata(&backup_path).map_err(|e| format!("Failed to get file metadata: {}", e))?;
+    let created_at = Local::now().to_rfc3339();
+
+    manifest::write(
+        &backup_path,
+        &BackupManifest {
+            filename: filename.clone(),
+            sha256: checksum.clone(),
+            size_bytes: metadata.len(),
+            uncompressed_size_bytes: Some(uncompressed_size),
+            app_version: crate::version::get_app_version(),
+            migration_version: summary.migration_version,
+            tables: summary.tables,
+            format,
+            attachments: attachments.clone(),
+            backup_type: Some(backup_type),
+            compression,
+            encrypted,
+            created_at: created_at.clone(),
+        },
+    )?;
+
+    // Off-site copies are best effort; failures are reported, not fatal
+    let uploads = {
+        let app = app.clone();
+        let files = [backup_path.clone(), manifest::manifest_path(&backup_path)];
+        tauri::async_runtime::spawn_blocking(move || destination::upload_all(&app, &files, &job))
+            .await
+            .map_err(|e| format!("Upload task failed: {}", e))?
+    };
+
+    // A failed prune must not turn a good backup into a failed one
+    let pruned = if prune {
+        retention::apply(&app, &backup_dir).unwrap_or_else(|e| {
+            log::warn!("Backup retention failed: {}", e);
+            Vec::new()
+        })
+    } else {
+        Vec::new()
+    };
+
+    Ok(BackupOutcome {
+        info: BackupInfo {
+            filename,
+            size_bytes: metadata.len(),
+            created_at: Some(created_at),
+            backup_type: Some(backup_type),
+            format,
+            compression,
+            encrypted,
+            checksum: Some(checksum),
+            uncompressed_size_bytes: Some(uncompressed_size),
+        },
+        path: backup_path,
+        requires_files_key: !attachments.is_empty(),
+        attachments,
+        missing_attachments,
+        pruned,
+        uploads,
+    })
+}
+
+/// Options of a manual backup, shared by the blocking command and `start_backup_job`.
+struct BackupParams {
+    backup_type: BackupType,
+    format: BackupFormat,
+    compression: Compression,
+    passphrase: Option<String>,
+    engine: BackupEngine,
+}
+
+impl BackupParams {
+    /// Checks the options given to a backup command before the job starts.
+    fn new(
+        backup_type: BackupType,
+        format: Option<BackupFormat>,
+        compression: Option<Compression>,
+        passphrase: Option<String>,
+        engine: Option<BackupEngine>,
+    ) -> Result<Self, BackupError> {
+        if let Some(passphrase) = &passphrase {
+            encryption::validate_passphrase(passphrase).map_err(BackupError::Passphrase)?;
+        }
+        Ok(BackupParams {
+            backup_type,
+            format: format.unwrap_or_default(),
+            compression: compression.unwrap_or_default(),
+            passphrase,
+            engine: engine.unwrap_or_default(),
+        })
+    }
+}
+
+#[command]
+pub async fn create_database_backup(
+    app: AppHandle,
+    db: State<'_, DbConfigState>,
+    backup_type: BackupType,
+    format: Option<BackupFormat>,
+    compression: Option<Compression>,
+    passphrase: Option<String>,
+    engine: Option<BackupEngine>,
+) -> Result<BackupOutcome, BackupError> {
+    let config = db.current()?;
+    let params = BackupParams::new(backup_type, format, compression, passphrase, engine)?;
+    let job = app.state::<JobManager>().start(JobKind::Backup)?;
+
+    let outcome = create_backup(&app, config, params, job.token.clone()).await;
+    job.finish(outcome)
+}
+
+/// Runs a manual backup and records it in `backup_history`, failed or not.
+async fn create_backup(
+    app: &AppHandle,
+    config: DbConfig,
+    params: BackupParams,
+    job: JobToken,
+) -> Result<BackupOutcome, BackupError> {
+    let BackupParams {
+        backup_type,
+        format,
+        compression,
+        passphrase,
+        engine,
+    } = params;
+    let filename = backup_filename(&config.database, format, compression, passphrase.is_some());
+
+    let outcome = perform_backup(
+        app.clone(),
+        config.clone(),
+        BackupRequest {
+            filename: filename.clone(),
+            backup_type,
+            format,
+            compression,
+            passphrase,
+            engine,
+            prune: true,
+            job,
+        },
+    )
+    .await
+    .map_err(BackupError::from);
+
+    let size = outcome.as_ref().ok().map(|o| o.info.size_bytes);
+    let error = outcome.as_ref().err().map(ToString::to_string);
+    history::record_logged(&config, backup_type, &filename, size, error.as_deref()).await;
+
+    outcome
+}
+
+/// How a backup file is laid out and which engine produces its dump.
+struct DumpOptions<'a> {
+    format: BackupFormat,
+    compression: Compression,
+    passphrase: Option<&'a str>,
+    engine: BackupEngine,
+    job: &'a JobToken,
+}
+
+/// Writes the backup file, returning its uncompressed size and any
+/// attachments that could not be found.
+fn dump_to_file(
+    app: &AppHandle,
+    config: &DbConfig,
+    filename: &str,
+    backup_path: &Path,
+    attachments: &[String],
+    options: &DumpOptions,
+) -> Result<(u64, Vec<String>), String> {
+    let mut writer = BackupWriter::create(backup_path, options.compression, options.passphrase)
+        .map_err(|e| format!("Failed to create backup file: {}", e))?;
+    let report = emit_progress(app, "backup", filename, None, options.job);
+
+    let dump = |writer: &mut dyn Write| {
+        let mut writer = writer;
+        if options.engine.use_native(client::MYSQLDUMP) {
+            tauri::async_runtime::block_on(native::dump(config, &mut writer, report, options.job))
+        } else {
+            client::dump(config, &mut writer, report, options.job)
+        }
+    };
+
+    let result = match options.format {
+        BackupFormat::Sql => (dump(&mut writer)?, Vec::new()),
+        BackupFormat::Full => {
+            // tar needs each entry's size up front, so the dump is staged first
+            let backup_dir = backup_path.parent().unwrap_or(Path::new("."));
+            let mut staged = tempfile::NamedTempFile::new_in(backup_dir)
+                .map_err(|e| format!("Failed to create temporary dump file: {}", e))?;
+            {
+                let mut staged_writer = io::BufWriter::new(staged.as_file_mut());
+                dump(&mut staged_writer)?;
+                staged_writer
+                    .flush()
+                    .map_err(|e| format!("Failed to write temporary dump file: {}", e))?;
+            }
+            let app_dir = app
+                .path()
+                .app_data_dir()
+                .map_err(|e| format!("Failed to get app directory: {}", e))?;
+            archive::write(&mut writer, &app_dir, attachments, staged.path())?
+        }
+    };
+
+    writer
+        .finish()
+        .map_err(|e| format!("Failed to write backup file: {}", e))?;
+    Ok(result)
+}
+
+/// Opens a backup for reading, undoing encryption and compression as implied by its extension.
+fn open_backup(
+    backup_path: &Path,
+    filename: &str,
+    passphrase: Option<&str>,
+) -> Result<(Box<dyn BufRead + Send>, BackupName), String> {
+    let name = parse_backup_name(filename)
+        .ok_or_else(|| format!("Unsupported backup file type: {}", filename))?;
+    let input = encryption::open_input(backup_path, name.encrypted, passphrase)?;
+    let reader = compression::open_reader(input, name.compression)
+        .map_err(|e| format!("Failed to read backup file: {}", e))?;
+    Ok((reader, name))
+}
+
+/// How to replay a backup file.
+#[derive(Clone, Default)]
+struct RestoreOptions {
+    passphrase: Option<String>,
+    engine: BackupEngine,
+    /// Also put back the medical files of a full backup.
+    attachments: bool,
+    /// Live tables absent from an older backup. Dropped once the snapshot is
+    /// taken so the migrations that created them run again.
+    drop_tables: Vec<String>,
+    job: JobToken,
+}
+
+/// Restores a backup after taking a safety snapshot of the live database
+/// through the regular backup path. If the restore fails midway, or is
+/// cancelled, the snapshot is replayed so the database is never left half
+/// imported. The restore, the snapshot and any rollback are each recorded in
+/// `backup_history`.
+async fn restore_with_snapshot(
+    app: &AppHandle,
+    config: &DbConfig,
+    filename: &str,
+    backup_path: PathBuf,
+    options: RestoreOptions,
+) -> Result<usize, BackupError> {
+    let size = fs::metadata(&backup_path).ok().map(|m| m.len());
+    let restored = snapshot_and_restore(app, config, filename, backup_path, options).await;
+    let error = restored.as_ref().err().map(ToString::to_string);
+    history::record_logged(
+        config,
+        BackupType::Restore,
+        filename,
+        size,
+        error.as_deref(),
+    )
+    .await;
+    restored
+}
+
+async fn snapshot_and_restore(
+    app: &AppHandle,
+    config: &DbConfig,
+    filename: &str,
+    backup_path: PathBuf,
+    options: RestoreOptions,
+) -> Result<usize, BackupError> {
+    let engine = options.engine;
+    let snapshot_name = backup_filename(
+        &config.database,
+        BackupFormat::Sql,
+        Compression::Gzip,
+        false,
+    );
+    let snapshot = perform_backup(
+        app.clone(),
+        config.clone(),
+        BackupRequest {
+            filename: snapshot_name.clone(),
+            backup_type: BackupType::PreRestore,
+            format: BackupFormat::Sql,
+            compression: Compression::Gzip,
+            passphrase: None,
+            engine,
+            prune: false,
+            job: options.job.clone(),
+        },
+    )
+    .await;
+
+    // The restore replaces backup_history, so the snapshot is only recorded
+    // once the live tables are final
+    let snapshot = match snapshot {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            history::record_logged(
+                config,
+                BackupType::PreRestore,
+                &snapshot_name,
+                None,
+                Some(&e),
+            )
+            .await;
+            // Without a snapshot there is nothing to roll back to
+            return Err(BackupError::SnapshotFailed(e));
+        }
+    };
+
+    let restored = {
+        let app = app.clone();
+        let config = config.clone();
+        let filename = filename.to_string();
+        tauri::async_runtime::spawn_blocking(move || {
+            // The snapshot still holds these tables, so a rollback brings them back
+            if !options.drop_tables.is_empty() {
+                tauri::async_runtime::block_on(async {
+                    let mut conn = crate::db_config::connect(&config).await?;
+                    manifest::drop_tables(&mut conn, &options.drop_tables).await
+                })?;
+            }
+            restore_from_file(&app, &config, &filename, &backup_path, &options)
+        })
+        .await
+        .map_err(|e| format!("Restore task failed: {}", e))
+        .and_then(|result| result)
+    };
+    let snapshot_size = Some(snapshot.info.size_bytes);
+    let error = match restored {
+        Ok(attachments) => {
+            history::record_logged(
+                config,
+                BackupType::PreRestore,
+                &snapshot_name,
+                snapshot_size,
+                None,
+            )
+            .await;
+            return Ok(attachments);
+        }
+        Err(e) => e,
+    };
+
+    // Not tied to the job, so a cancelled restore still rolls back
+    let rollback = RestoreOptions {
+        engine,
+        ..RestoreOptions::default()
+    };
+    let rolled_back = {
+        let app = app.clone();
+        let config = config.clone();
+        let snapshot_name = snapshot_name.clone();
+        let snapshot_path = snapshot.path.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            restore_from_file(&app, &config, &snapshot_name, &snapshot_path, &rollback)
+        })
+        .await
+        .map_err(|e| format!("Rollback task failed: {}", e))
+        .and_then(|result| result)
+    };
+    history::record_logged(
+        config,
+        BackupType::PreRestore,
+        &snapshot_name,
+        snapshot_size,
+        None,
+    )
+    .await;
+    history::record_logged(
+        config,
+        BackupType::Rollback,
+        &snapshot.info.filename,
+        snapshot_size,
+        rolled_back.as_ref().err().map(String::as_str),
+    )
+    .await;
+
+    let snapshot = snapshot.info.filename;
+    Err(match rolled_back {
+        Ok(_) => BackupError::RolledBack { snapshot, error },
+        Err(rollback_error) => BackupError::RollbackFailed {
+            snapshot,
+            error,
+            rollback_error,
+        },
+    })
+}
+
+fn restored_message(filename: &str, attachments: usize) -> String {
+    if attachments > 0 {
+        return format!(
+            "Database and {} medical files restored successfully from {}",
+            attachments, filename
+        );
+    }
+    format!("Database restored successfully from {}", filename)
+}
+
+fn restore_from_file(
+    app: &AppHandle,
+    config: &DbConfig,
+    filename: &str,
+    backup_path: &Path,
+    options: &RestoreOptions,
+) -> Result<usize, String> {
+    let passphrase = options.passphrase.as_deref();
+    let (mut reader, name) = open_backup(backup_path, filename, passphrase)?;
+
+    // Read the whole file before touching the database: a tampered archive
+    // is rejected instead of being half imported, and a dump naming its own
+    // database could otherwise leave the schema it is restored into.
+    let statement = match name.format {
+        BackupFormat::Sql => native::find_database_statement(&mut *reader),
+        BackupFormat::Full => archive::check_dump(&mut reader, native::find_database_statement)
+            .map(Option::flatten)
+            .and_then(|found| io::copy(&mut reader, &mut io::sink()).map(|_| found)),
+    }
+    .map_err(|e| {
+        encryption::describe_read_error(&e)
+            .unwrap_or_else(|| format!("Failed to read backup file: {}", e))
+    })?;
+    if let Some(statement) = statement {
+        let preview: String = statement.chars().take(120).collect();
+        return Err(format!(
+            "Backup selects or creates its own database and cannot be restored here: {}",
+            preview
+        ));
+    }
+    reader = open_backup(backup_path, filename, passphrase)?.0;
+
+    // Only plain dumps know their decompressed length up front
+    let total_bytes = match (name.format, name.compression, name.encrypted) {
+        (BackupFormat::Sql, Compression::None, false) => {
+            fs::metadata(backup_path).ok().map(|m| m.len())
+        }
+        _ => None,
+    };
+
+    let report = emit_progress(app, "restore", filename, total_bytes, &options.job);
+    let restore_dump = |reader: &mut dyn BufRead| {
+        let mut reader = reader;
+        if options.engine.use_native(client::MYSQL) {
+            tauri::async_runtime::block_on(native::restore(
+                config,
+                &mut reader,
+                report,
+                &options.job,
+            ))?;
+        } else {
+            client::restore(config, &mut reader, report, &options.job)?;
+        }
+        Ok(())
+    };
+
+    match name.format {
+        BackupFormat::Sql => restore_dump(&mut reader).map(|_| 0),
+        BackupFormat::Full if !options.attachments => archive::restore(None, reader, restore_dump),
+        BackupFormat::Full => {
+            let app_dir = app
+                .path()
+                .app_data_dir()
+                .map_err(|e| format!("Failed to get app directory: {}", e))?;
+            fs::create_dir_all(&app_dir)
+                .map_err(|e| format!("Failed to create app directory: {}", e))?;
+            archive::restore(Some(&app_dir), reader, restore_dump)
+        }
+    }
+}
+
+/// Extracts the listed attachments of a full backup into `app_dir` without
+/// touching the database. Returns how many were written; always 0 for SQL
+/// backups.
+fn restore_attachments(
+    app_dir: &Path,
+    filename: &str,
+    backup_path: &Path,
+    passphrase: Option<&str>,
+    files: &BTreeSet<String>,
+) -> Result<usize, String> {
+    let (reader, name) = open_backup(backup_path, filename, passphrase)?;
+    if name.format != BackupFormat::Full {
+        return Ok(0);
+    }
+    archive::extract(app_dir, reader, files)
+}
+
+#[command]
+pub async fn list_backup_files(app: AppHandle) -> Result<Vec<BackupInfo>, BackupError> {
+    let backup_dir = location::backup_dir(&app)?;
+    let mut backups = Vec::new();
+
+    let entries =
+        fs::read_dir(&backup_dir).map_err(|e| format!("Failed to read backup directory: {}", e))?;
+
+    for entry in entries.flatten() {
+        if let Some(filename) = entry.file_name().to_str() {
+            if parse_backup_name(filename).is_some() {
+                // One file that cannot be read must not hide all the others
+                match BackupInfo::read(&entry.path(), filename) {
+                    Ok(info) => backups.push(info),
+                    Err(e) => log::warn!("Skipping backup {}: {}", filename, e),
+                }
+            }
+        }
+    }
+
+    Ok(backups)
+}
+
+/// Resolves `filename` inside the backup directory, failing if it is not there.
+fn existing_backup(app: &AppHandle, filename: &str) -> Result<PathBuf, BackupError> {
+    let backup_path = safe_path::resolve(&location::backup_dir(app)?, filename)?;
+    if !backup_path.exists() {
+        return Err(BackupError::NotFound(filename.to_string()));
+    }
+    Ok(backup_path)
+}
+
+/// Deletes a backup together with its manifest sidecar.
+fn remove_backup(backup_path: &Path) -> Result<(), String> {
+    fs::remove_file(backup_path).map_err(|e| format!("Failed to delete backup file: {}", e))?;
+
+    let manifest_path = manifest::manifest_path(backup_path);
+    if manifest_path.exists() {
+        fs::remove_file(&manifest_path)
+            .map_err(|e| format!("Failed to delete backup manifest: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Deletes a backup, returning what was deleted.
+#[command]
+pub async fn delete_backup_file(
+    app: AppHandle,
+    filename: String,
+) -> Result<BackupInfo, BackupError> {
+    let backup_path = existing_backup(&app, &filename)?;
+    let info = BackupInfo::read(&backup_path, &filename)?;
+
+    remove_backup(&backup_path)?;
+
+    Ok(info)
+}
+
+#[command]
+pub async fn verify_backup_file(
+    app: AppHandle,
+    filename: String,
+    passphrase: Option<String>,
+) -> Result<VerificationReport, BackupError> {
+    let backup_path = existing_backup(&app, &filename)?;
+
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        verify_file(&filename, &backup_path, passphrase.as_deref())
+    })
+    .await
+    .map_err(|e| format!("Verification task failed: {}", e))?;
+
+    Ok(report)
+}
+
+fn verify_file(filename: &str, backup_path: &Path, passphrase: Option<&str>) -> VerificationReport {
+    let mut report = VerificationReport {
+        filename: filename.to_string(),
+        valid: false,
+        manifest_found: false,
+        checksum_ok: None,
+        header_ok: None,
+        complete: None,
+        errors: Vec::new(),
+    };
+
+    match manifest::read(backup_path) {
+        Ok(Some(expected)) => {
+            report.manifest_found = true;
+            match manifest::sha256_file(backup_path) {
+                Ok(actual) => {
+                    let ok = actual.eq_ignore_ascii_case(&expected.sha256);
+                    if !ok {
+                        report
+                            .errors
+                            .push("Checksum does not match the manifest".to_string());
+                    }
+                    report.checksum_ok = Some(ok);
+                }
+                Err(e) => report
+                    .errors
+                    .push(format!("Failed to hash backup file: {}", e)),
+            }
+        }
+        Ok(None) => {}
+        Err(e) => report.errors.push(e),
+    }
+
+    let (_, encrypted) = encryption::strip_extension(filename);
+    if encrypted && passphrase.is_none() {
+        // Without the passphrase only the checksum can be checked
+        report.valid = report.errors.is_empty();
+        return report;
+    }
+
+    let checked = open_backup(backup_path, filename, passphrase).map(|(mut reader, name)| {
+        match name.format {
+            BackupFormat::Sql => manifest::check_dump(&mut reader),
+            // A full backup without a dump entry counts as a missing header
+            BackupFormat::Full => {
+                archive::check_dump(reader, |mut dump| manifest::check_dump(&mut dump))
+                    .map(|checked| checked.unwrap_or((false, false)))
+            }
+        }
+    });
+
+    match checked {
+        Ok(checked) => match checked {
+            Ok((header_ok, complete)) => {
+                if !header_ok {
+                    report
+                        .errors
+                        .push("Dump header is missing or unrecognized".to_string());
+                }
+                if !complete {
+                    report
+                        .errors
+                        .push("Dump is truncated: completion marker not found".to_string());
+                }
+                report.header_ok = Some(header_ok);
+                report.complete = Some(complete);
+            }
+            Err(e) => report.errors.push(
+                encryption::describe_read_error(&e)
+                    .unwrap_or_else(|| format!("Failed to read backup file: {}", e)),
+            ),
+        },
+        Err(e) => report.errors.push(e),
+    }
+
+    report.valid = report.errors.is_empty();
+    report
+}
@@ -0,0 +1,769 @@
+use chrono::Local;
+use futures_util::TryStreamExt;
+use sqlx::mysql::{MySqlConnection, MySqlRow};
+use sqlx::Row;
+use std::io::{self, BufRead, Write};
+
+use super::job::JobToken;
+use super::manifest::{list_tables, quote_identifier};
+use super::{table_marker, PROGRESS_INTERVAL_BYTES};
+use crate::db_config::{self, DbConfig};
+
+/// Header written in place of mysqldump's so `verify_backup_file` recognises native dumps.
+pub const DUMP_HEADER: &str = "-- CardioPc dump";
+const INSERT_BATCH_BYTES: usize = 1024 * 1024;
+/// Session SQL mode the dump replays under, restored after each trigger or routine.
+const DUMP_SQL_MODE: &str = "NO_AUTO_VALUE_ON_ZERO";
+const BINARY_TYPES: &[&str] = &[
+    "binary",
+    "varbinary",
+    "tinyblob",
+    "blob",
+    "mediumblob",
+    "longblob",
+    "bit",
+    "geometry",
+];
+
+struct CountingWriter<'a, W: Write> {
+    inner: &'a mut W,
+    bytes: u64,
+}
+
+impl<W: Write> CountingWriter<'_, W> {
+    fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        self.inner
+            .write_all(data)
+            .map_err(|e| format!("Failed to write backup file: {}", e))?;
+        self.bytes += data.len() as u64;
+        Ok(())
+    }
+}
+
+/// Text column of a `SHOW CREATE` result, which servers report as either
+/// a string or a binary string.
+fn text_column(row: &MySqlRow, column: &str) -> Result<Option<String>, String> {
+    row.try_get::<Option<String>, _>(column)
+        .or_else(|_| {
+            row.try_get::<Option<Vec<u8>>, _>(column)
+                .map(|bytes| bytes.map(|b| String::from_utf8_lossy(&b).into_owned()))
+        })
+        .map_err(|e| format!("Failed to read {}: {}", column, e))
+}
+
+/// A trigger or routine definition, wrapped in a `DELIMITER` change since
+/// its body holds `;`, and created under the SQL mode it was defined with.
+fn compound_statement(create: &str, sql_mode: &str) -> String {
+    format!(
+        "SET SESSION sql_mode = '{}';\nDELIMITER ;;\n{} ;;\nDELIMITER ;\nSET SESSION sql_mode = '{}';\n",
+        sql_mode.replace('\'', "''"),
+        create,
+        DUMP_SQL_MODE
+    )
+}
+
+/// Value expression producing an SQL literal for one column. Binary columns are
+/// hex-encoded so the dump stays valid UTF-8 and can be replayed over sqlx.
+fn literal_expression(column: &str, data_type: &str) -> String {
+    let column = quote_identifier(column);
+    if BINARY_TYPES.contains(&data_type.to_ascii_lowercase().as_str()) {
+        format!(
+            "IF({0} IS NULL, 'NULL', CONCAT('X''', HEX({0}), ''''))",
+            column
+        )
+    } else {
+        format!("QUOTE({})", column)
+    }
+}
+
+async fn dump_table<W: Write>(
+    conn: &mut MySqlConnection,
+    table: &str,
+    out: &mut CountingWriter<'_, W>,
+    last_report: &mut u64,
+    report: &mut impl FnMut(u64, u32, Option<String>),
+    tables_done: u32,
+    job: &JobToken,
+) -> Result<(), String> {
+    let quoted = quote_identifier(table);
+
+    let (_, create): (String, String) = sqlx::query_as(&format!("SHOW CREATE TABLE {}", quoted))
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to read structure of {}: {}", table, e))?;
+
+    out.write(
+        format!(
+            "\n--\n-- Table structure for table `{}`\n--\n\nDROP TABLE IF EXISTS {};\n{};\n",
+            table, quoted, create
+        )
+        .as_bytes(),
+    )?;
+    report(out.bytes, tables_done, Some(table.to_string()));
+    *last_report = out.bytes;
+
+    let columns: Vec<(String, String)> = sqlx::query_as(
+        "SELECT CAST(column_name AS CHAR), CAST(data_type AS CHAR)
+         FROM information_schema.columns
+         WHERE table_schema = DATABASE() AND table_name = ?
+           AND extra NOT IN ('VIRTUAL GENERATED', 'STORED GENERATED')
+         ORDER BY ordinal_position",
+    )
+    .bind(table)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to read columns of {}: {}", table, e))?;
+
+    if columns.is_empty() {
+        return Ok(());
+    }
+
+    let column_list = columns
+        .iter()
+        .map(|(name, _)| quote_identifier(name))
+        .collect::<Vec<_>>()
+        .join(",");
+    let values = columns
+        .iter()
+        .map(|(name, data_type)| literal_expression(name, data_type))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let select = format!(
+        "SELECT CONCAT('(', CONCAT_WS(',', {}), ')') FROM {}",
+        values, quoted
+    );
+    let insert_prefix = format!("INSERT INTO {} ({}) VALUES ", quoted, column_list);
+
+    out.write(format!("\n--\n-- Dumping data for table `{}`\n--\n\n", table).as_bytes())?;
+
+    let mut batch: Vec<u8> = Vec::with_capacity(INSERT_BATCH_BYTES);
+    let mut rows = sqlx::query_scalar::<_, Vec<u8>>(&select).fetch(&mut *conn);
+    while let Some(row) = rows
+        .try_next()
+        .await
+        .map_err(|e| format!("Failed to read rows of {}: {}", table, e))?
+    {
+        if batch.is_empty() {
+            batch.extend_from_slice(insert_prefix.as_bytes());
+        } else {
+            batch.push(b',');
+        }
+        batch.extend_from_slice(&row);
+
+        if batch.len() >= INSERT_BATCH_BYTES {
+            batch.extend_from_slice(b";\n");
+            out.write(&batch)?;
+            batch.clear();
+            job.check()?;
+        }
+
+        if out.bytes - *last_report >= PROGRESS_INTERVAL_BYTES {
+            report(out.bytes, tables_done, None);
+            *last_report = out.bytes;
+        }
+    }
+
+    if !batch.is_empty() {
+        batch.extend_from_slice(b";\n");
+        out.write(&batch)?;
+    }
+    drop(rows);
+
+    dump_triggers(conn, table, out).await
+}
+
+/// Triggers of `table`, written after its rows so replaying them does not fire.
+async fn dump_triggers<W: Write>(
+    conn: &mut MySqlConnection,
+    table: &str,
+    out: &mut CountingWriter<'_, W>,
+) -> Result<(), String> {
+    let triggers: Vec<String> = sqlx::query_scalar(
+        "SELECT CAST(trigger_name AS CHAR) FROM information_schema.triggers
+         WHERE trigger_schema = DATABASE() AND event_object_table = ?
+         ORDER BY action_timing, event_manipulation, action_order",
+    )
+    .bind(table)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to list triggers of {}: {}", table, e))?;
+
+    for trigger in triggers {
+        let row = sqlx::query(&format!(
+            "SHOW CREATE TRIGGER {}",
+            quote_identifier(&trigger)
+        ))
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to read trigger {}: {}", trigger, e))?;
+        let create = text_column(&row, "SQL Original Statement")?
+            .ok_or_else(|| format!("Not allowed to read the definition of trigger {}", trigger))?;
+        let sql_mode = text_column(&row, "sql_mode")?.unwrap_or_default();
+
+        out.write(format!("\n--\n-- Trigger `{}`\n--\n\n", trigger).as_bytes())?;
+        out.write(compound_statement(&create, &sql_mode).as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Stored procedures and functions, which mysqldump includes with `--routines`.
+async fn dump_routines<W: Write>(
+    conn: &mut MySqlConnection,
+    out: &mut CountingWriter<'_, W>,
+) -> Result<(), String> {
+    let routines: Vec<(String, String)> = sqlx::query_as(
+        "SELECT CAST(routine_name AS CHAR), CAST(routine_type AS CHAR)
+         FROM information_schema.routines
+         WHERE routine_schema = DATABASE() AND routine_type IN ('PROCEDURE', 'FUNCTION')
+         ORDER BY routine_type, routine_name",
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to list stored routines: {}", e))?;
+
+    for (name, kind) in routines {
+        let quoted = quote_identifier(&name);
+        let row = sqlx::query(&format!("SHOW CREATE {} {}", kind, quoted))
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to read {} {}: {}", kind.to_lowercase(), name, e))?;
+        let (label, column) = if kind == "FUNCTION" {
+            ("Function", "Create Function")
+        } else {
+            ("Procedure", "Create Procedure")
+        };
+        // NULL when the account lacks the privilege to see the body
+        let create = text_column(&row, column)?.ok_or_else(|| {
+            format!(
+                "Not allowed to read the definition of {} {}",
+                kind.to_lowercase(),
+                name
+            )
+        })?;
+        let sql_mode = text_column(&row, "sql_mode")?.unwrap_or_default();
+
+        out.write(
+            format!(
+                "\n--\n-- {} `{}`\n--\n\nDROP {} IF EXISTS {};\n",
+                label, name, kind, quoted
+            )
+            .as_bytes(),
+        )?;
+        out.write(compound_statement(&create, &sql_mode).as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Views with their column names.
+async fn list_views(conn: &mut MySqlConnection) -> Result<Vec<(String, Vec<String>)>, String> {
+    let names: Vec<String> = sqlx::query_scalar(
+        "SELECT CAST(table_name AS CHAR) FROM information_schema.tables
+         WHERE table_schema = DATABASE() AND table_type = 'VIEW'
+         ORDER BY table_name",
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to list views: {}", e))?;
+
+    let mut views = Vec::with_capacity(names.len());
+    for name in names {
+        let columns: Vec<String> = sqlx::query_scalar(
+            "SELECT CAST(column_name AS CHAR) FROM information_schema.columns
+             WHERE table_schema = DATABASE() AND table_name = ?
+             ORDER BY ordinal_position",
+        )
+        .bind(&name)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to read columns of view {}: {}", name, e))?;
+        views.push((name, columns));
+    }
+    Ok(views)
+}
+
+/// Stand-in for a view so views selecting from it can be created before it;
+/// replaced by the definition at the end of the dump, as mysqldump does.
+fn view_placeholder(view: &str, columns: &[String]) -> String {
+    let quoted = quote_identifier(view);
+    let columns = columns
+        .iter()
+        .map(|c| format!("1 AS {}", quote_identifier(c)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "\n--\n-- Temporary view structure for view `{}`\n--\n\nDROP TABLE IF EXISTS {1};\nDROP VIEW IF EXISTS {1};\nCREATE VIEW {1} AS SELECT {2};\n",
+        view, quoted, columns
+    )
+}
+
+async fn dump_view<W: Write>(
+    conn: &mut MySqlConnection,
+    view: &str,
+    out: &mut CountingWriter<'_, W>,
+) -> Result<(), String> {
+    let quoted = quote_identifier(view);
+    let row = sqlx::query(&format!("SHOW CREATE VIEW {}", quoted))
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to read view {}: {}", view, e))?;
+    let create = text_column(&row, "Create View")?
+        .ok_or_else(|| format!("Not allowed to read the definition of view {}", view))?;
+
+    out.write(
+        format!(
+            "\n--\n-- Final view structure for view `{}`\n--\n\nDROP VIEW IF EXISTS {};\n{};\n",
+            view, quoted, create
+        )
+        .as_bytes(),
+    )
+}
+
+/// Logical dump over the same driver as the SQL plugin, taken inside a
+/// REPEATABLE READ snapshot so every table reflects the same instant.
+pub async fn dump<W: Write>(
+    config: &DbConfig,
+    writer: &mut W,
+    mut report: impl FnMut(u64, u32, Option<String>),
+    job: &JobToken,
+) -> Result<u64, String> {
+    let mut conn = db_config::connect(config).await?;
+
+    // TIMESTAMP columns are read in the session time zone; dumping in UTC and
+    // replaying in UTC keeps them from shifting when the server zone differs.
+    sqlx::raw_sql("SET time_zone = '+00:00'")
+        .execute(&mut conn)
+        .await
+        .map_err(|e| format!("Failed to set session time zone: {}", e))?;
+    sqlx::raw_sql("SET SESSION TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+        .execute(&mut conn)
+        .await
+        .map_err(|e| format!("Failed to set isolation level: {}", e))?;
+    sqlx::raw_sql("START TRANSACTION WITH CONSISTENT SNAPSHOT, READ ONLY")
+        .execute(&mut conn)
+        .await
+        .map_err(|e| format!("Failed to start snapshot transaction: {}", e))?;
+
+    let tables = list_tables(&mut conn).await?;
+    let views = list_views(&mut conn).await?;
+
+    let mut out = CountingWriter {
+        inner: writer,
+        bytes: 0,
+    };
+    out.write(
+        format!(
+            "{} {}\n--\n-- Host: {}    Database: {}\n-- ------------------------------------------------------\n\n\
+             /*!40101 SET NAMES utf8mb4 */;\n\
+             SET TIME_ZONE='+00:00';\n\
+             SET FOREIGN_KEY_CHECKS=0;\n\
+             SET UNIQUE_CHECKS=0;\n\
+             SET SQL_MODE='{}';\n",
+            DUMP_HEADER,
+            crate::version::get_app_version(),
+            config.host,
+            config.database,
+            DUMP_SQL_MODE
+        )
+        .as_bytes(),
+    )?;
+
+    let mut last_report = 0u64;
+    for (index, table) in tables.iter().enumerate() {
+        job.check()?;
+        dump_table(
+            &mut conn,
+            table,
+            &mut out,
+            &mut last_report,
+            &mut report,
+            index as u32 + 1,
+            job,
+        )
+        .await?;
+    }
+
+    for (view, columns) in &views {
+        out.write(view_placeholder(view, columns).as_bytes())?;
+    }
+    job.check()?;
+    dump_routines(&mut conn, &mut out).await?;
+    for (view, _) in &views {
+        job.check()?;
+        dump_view(&mut conn, view, &mut out).await?;
+    }
+
+    out.write(
+        format!(
+            "\nSET FOREIGN_KEY_CHECKS=1;\nSET UNIQUE_CHECKS=1;\n\n-- Dump completed on {}\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S")
+        )
+        .as_bytes(),
+    )?;
+
+    let _ = sqlx::raw_sql("COMMIT").execute(&mut conn).await;
+
+    out.inner
+        .flush()
+        .map_err(|e| format!("Failed to write backup file: {}", e))?;
+    report(out.bytes, tables.len() as u32, None);
+    Ok(out.bytes)
+}
+
+/// Splits a dump into statements the way the mysql client does: honouring
+/// quotes, block comments, `--` line comments and `DELIMITER` changes.
+struct StatementSplitter {
+    buffer: String,
+    delimiter: String,
+    quote: Option<char>,
+    in_block_comment: bool,
+}
+
+impl StatementSplitter {
+    fn new() -> Self {
+        StatementSplitter {
+            buffer: String::new(),
+            delimiter: ";".to_string(),
+            quote: None,
+            in_block_comment: false,
+        }
+    }
+
+    fn at_statement_start(&self) -> bool {
+        self.quote.is_none() && !self.in_block_comment && self.buffer.trim().is_empty()
+    }
+
+    fn push_line(&mut self, line: &str) -> Vec<String> {
+        let mut statements = Vec::new();
+
+        if self.at_statement_start() {
+            let trimmed = line.trim_start();
+            if let Some(prefix) = trimmed.get(..10) {
+                if prefix.eq_ignore_ascii_case("DELIMITER ") {
+                    self.delimiter = trimmed[10..].trim().to_string();
+                    return statements;
+                }
+            }
+            if trimmed.starts_with("-- ") || trimmed.trim_end() == "--" || trimmed.starts_with('#')
+            {
+                return statements;
+            }
+        }
+
+        let mut rest = line;
+        while !rest.is_empty() {
+            let mut chars = rest.char_indices();
+            let mut consumed = None;
+
+            while let Some((i, c)) = chars.next() {
+                if let Some(quote) = self.quote {
+                    if c == '\\' && quote != '`' {
+                        chars.next();
+                    } else if c == quote {
+                        self.quote = None;
+                    }
+                } else if self.in_block_comment {
+                    if rest[i..].starts_with("*/") {
+                        self.in_block_comment = false;
+                        chars.next();
+                    }
+                } else if c == '\'' || c == '"' || c == '`' {
+                    self.quote = Some(c);
+                } else if rest[i..].starts_with("/*") {
+                    self.in_block_comment = true;
+                    chars.next();
+                } else if rest[i..].starts_with(&self.delimiter) {
+                    self.buffer.push_str(&rest[..i]);
+                    let statement = self.buffer.trim().to_string();
+                    if !statement.is_empty() {
+                        statements.push(statement);
+                    }
+                    self.buffer.clear();
+                    consumed = Some(i + self.delimiter.len());
+                    break;
+                }
+            }
+
+            match consumed {
+                Some(end) => rest = &rest[end..],
+                None => {
+                    self.buffer.push_str(rest);
+                    break;
+                }
+            }
+        }
+
+        statements
+    }
+
+    fn finish(self) -> Option<String> {
+        let statement = self.buffer.trim().to_string();
+        (!statement.is_empty()).then_some(statement)
+    }
+}
+
+/// First two keywords of a statement, looking inside versioned comments
+/// (`/*!40000 DROP DATABASE ...*/`) since the server runs their content.
+fn leading_keywords(statement: &str) -> Vec<String> {
+    let mut keywords = Vec::new();
+    let mut rest = statement;
+    while keywords.len() < 2 {
+        rest = rest.trim_start();
+        if let Some(versioned) = rest.strip_prefix("/*!") {
+            rest = versioned.trim_start_matches(|c: char| c.is_ascii_digit());
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.split_once("*/").map_or("", |(_, after)| after);
+        } else {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            if end == 0 {
+                break;
+            }
+            keywords.push(rest[..end].to_ascii_uppercase());
+            rest = &rest[end..];
+        }
+    }
+    keywords
+}
+
+/// Whether `statement` switches to, creates or drops a database, which would
+/// take the rest of a dump out of the schema it is replayed into.
+fn targets_database(statement: &str) -> bool {
+    match leading_keywords(statement).as_slice() {
+        [first, ..] if first == "USE" => true,
+        [first, second] => {
+            (first == "CREATE" || first == "DROP") && (second == "DATABASE" || second == "SCHEMA")
+        }
+        _ => false,
+    }
+}
+
+/// Reads a dump to the end and returns its first `USE`, `CREATE DATABASE` or
+/// `DROP DATABASE` statement, if any. Such dumps (`mysqldump --databases`)
+/// name their own schema and must not be replayed into another one.
+pub fn find_database_statement(reader: &mut dyn BufRead) -> io::Result<Option<String>> {
+    let mut splitter = StatementSplitter::new();
+    let mut line = Vec::with_capacity(64 * 1024);
+    let mut found = None;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if found.is_some() {
+            // Keep reading so an encrypted stream is authenticated to the end
+            continue;
+        }
+        found = splitter
+            .push_line(&String::from_utf8_lossy(&line))
+            .into_iter()
+            .find(|statement| targets_database(statement));
+    }
+    Ok(found.or_else(|| splitter.finish().filter(|s| targets_database(s))))
+}
+
+/// Replays a dump (native or mysqldump) statement by statement over sqlx.
+pub async fn restore<R: BufRead>(
+    config: &DbConfig,
+    reader: &mut R,
+    mut report: impl FnMut(u64, u32, Option<String>),
+    job: &JobToken,
+) -> Result<u64, String> {
+    let mut conn = db_config::connect(config).await?;
+    let mut splitter = StatementSplitter::new();
+    let mut line = Vec::with_capacity(64 * 1024);
+    let mut bytes = 0u64;
+    let mut last_report = 0u64;
+    let mut tables_done = 0u32;
+
+    loop {
+        line.clear();
+        let n = reader
+            .read_until(b'\n', &mut line)
+            .map_err(|e| format!("Failed to read backup file: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        bytes += n as u64;
+
+        if let Some(table) = table_marker(&line) {
+            tables_done += 1;
+            report(bytes, tables_done, Some(table));
+            last_report = bytes;
+        } else if bytes - last_report >= PROGRESS_INTERVAL_BYTES {
+            report(bytes, tables_done, None);
+            last_report = bytes;
+        }
+
+        let text = std::str::from_utf8(&line).map_err(|_| {
+            "Backup contains binary data that can only be restored with the mysql client"
+                .to_string()
+        })?;
+        for statement in splitter.push_line(text) {
+            job.check()?;
+            execute(&mut conn, &statement).await?;
+        }
+    }
+
+    if let Some(statement) = splitter.finish() {
+        execute(&mut conn, &statement).await?;
+    }
+
+    report(bytes, tables_done, None);
+    Ok(bytes)
+}
+
+async fn execute(conn: &mut MySqlConnection, statement: &str) -> Result<(), String> {
+    sqlx::raw_sql(statement)
+        .execute(&mut *conn)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            let preview: String = statement.chars().take(120).collect();
+            format!("Restore failed: {} (while executing: {})", e, preview)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(dump: &str) -> Vec<String> {
+        let mut splitter = StatementSplitter::new();
+        let mut statements: Vec<String> = dump
+            .split_inclusive('\n')
+            .flat_map(|line| splitter.push_line(line))
+            .collect();
+        statements.extend(splitter.finish());
+        statements
+    }
+
+    #[test]
+    fn ignores_delimiters_inside_quotes() {
+        assert_eq!(
+            split("INSERT INTO t VALUES ('a;b',\"c;d\");\nSELECT `odd;name` FROM t;\n"),
+            [
+                "INSERT INTO t VALUES ('a;b',\"c;d\")",
+                "SELECT `odd;name` FROM t",
+            ]
+        );
+    }
+
+    #[test]
+    fn handles_doubled_and_backslash_escaped_quotes() {
+        assert_eq!(
+            split("INSERT INTO t VALUES ('it''s; ok');\nINSERT INTO t VALUES ('it\\'s; ok', \"say \\\"hi;\\\"\");\n"),
+            [
+                "INSERT INTO t VALUES ('it''s; ok')",
+                "INSERT INTO t VALUES ('it\\'s; ok', \"say \\\"hi;\\\"\")",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_versioned_comments_as_statements() {
+        assert_eq!(
+            split("/*!40101 SET NAMES utf8mb4 */;\n/*!40103 SET TIME_ZONE='+00:00' */;\n"),
+            [
+                "/*!40101 SET NAMES utf8mb4 */",
+                "/*!40103 SET TIME_ZONE='+00:00' */",
+            ]
+        );
+    }
+
+    #[test]
+    fn follows_delimiter_changes_around_triggers() {
+        let dump = "DELIMITER ;;\n\
+                    CREATE TRIGGER stamp BEFORE INSERT ON t FOR EACH ROW BEGIN\n\
+                    \x20 SET NEW.a = 1;\n\
+                    \x20 SET NEW.b = 'x;;y';\n\
+                    END ;;\n\
+                    DELIMITER ;\n\
+                    INSERT INTO t VALUES (1);\n";
+        assert_eq!(
+            split(dump),
+            [
+                "CREATE TRIGGER stamp BEFORE INSERT ON t FOR EACH ROW BEGIN\n  SET NEW.a = 1;\n  SET NEW.b = 'x;;y';\nEND",
+                "INSERT INTO t VALUES (1)",
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_dumped_routines_with_their_sql_mode() {
+        let create = "CREATE DEFINER=`root`@`%` PROCEDURE `touch`()\nBEGIN\n  UPDATE t SET a = ';';\n  SELECT 1;\nEND";
+        assert_eq!(
+            split(&compound_statement(
+                create,
+                "STRICT_TRANS_TABLES,NO_ENGINE_SUBSTITUTION"
+            )),
+            [
+                "SET SESSION sql_mode = 'STRICT_TRANS_TABLES,NO_ENGINE_SUBSTITUTION'",
+                create,
+                "SET SESSION sql_mode = 'NO_AUTO_VALUE_ON_ZERO'",
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_view_placeholders_with_the_view_columns() {
+        let columns = ["id".to_string(), "odd`name".to_string()];
+        assert_eq!(
+            split(&view_placeholder("recent", &columns)),
+            [
+                "DROP TABLE IF EXISTS `recent`",
+                "DROP VIEW IF EXISTS `recent`",
+                "CREATE VIEW `recent` AS SELECT 1 AS `id`, 1 AS `odd``name`",
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_statements_leaving_the_target_database() {
+        let find = |dump: &str| find_database_statement(&mut dump.as_bytes()).unwrap();
+
+        let databases = "-- MySQL dump 10.13\n\
+                         /*!40000 DROP DATABASE IF EXISTS `cardiopc`*/;\n\
+                         CREATE DATABASE /*!32312 IF NOT EXISTS*/ `cardiopc`;\n\
+                         USE `cardiopc`;\n\
+                         DROP TABLE IF EXISTS `t`;\n";
+        assert_eq!(
+            find(databases).as_deref(),
+            Some("/*!40000 DROP DATABASE IF EXISTS `cardiopc`*/")
+        );
+        assert_eq!(
+            find("SELECT 1;\nuse`cardiopc`;\n").as_deref(),
+            Some("use`cardiopc`")
+        );
+        assert_eq!(
+            find("SELECT 1;\ncreate schema other\n").as_deref(),
+            Some("create schema other")
+        );
+
+        let plain = "DROP TABLE IF EXISTS `databases`;\n\
+                     INSERT INTO t VALUES ('USE other; DROP DATABASE x');\n\
+                     CREATE TABLE `use` (a INT);\n";
+        assert_eq!(find(plain), None);
+    }
+
+    #[test]
+    fn skips_comment_lines_between_statements() {
+        let dump = "-- MySQL dump 10.13\n\
+                    --\n\
+                    -- Table structure for table `t`\n\
+                    # written by hand\n\
+                    DROP TABLE IF EXISTS `t`;\n\
+                    SELECT '-- not a comment';\n\
+                    \n\
+                    -- Dump completed on 2024-01-01\n";
+        assert_eq!(
+            split(dump),
+            ["DROP TABLE IF EXISTS `t`", "SELECT '-- not a comment'"]
+        );
+    }
+
+    #[test]
+    fn returns_trailing_statement_without_delimiter() {
+        assert_eq!(split("SELECT 1;\nSELECT 2\n"), ["SELECT 1", "SELECT 2"]);
+    }
+}
@@ -0,0 +1,688 @@
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::mysql::MySqlConnection;
+use sqlx::Connection;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use tauri::{command, AppHandle, Manager, State};
+
+use super::job::{JobKind, JobManager};
+use super::manifest::quote_identifier;
+use super::{location, scratch, BackupEngine, BackupError, RestoreOptions};
+use crate::db_config::{self, DbConfigState};
+use crate::file_storage;
+use crate::safe_path;
+
+const SCRATCH_SUFFIX: &str = "_patient_restore";
+/// Tables holding a `patient_db_id`, copied after the patient row.
+const PATIENT_TABLES: [&str; 3] = ["emergency_contacts", "risk_factors", "appointments"];
+/// Tables holding a `consultation_id`, copied after each consultation.
+const CONSULTATION_TABLES: [&str; 5] = [
+    "clinical_exams",
+    "diagnostic_results",
+    "ecg_ett_exams",
+    "scores",
+    "prescriptions",
+];
+
+#[derive(Debug, Serialize)]
+pub struct PatientRestoreReport {
+    pub patient_id: String,
+    /// `patients.id` of the re-imported patient; all ids are newly assigned.
+    pub patient_db_id: u64,
+    /// Rows inserted per table.
+    pub rows: BTreeMap<String, usize>,
+    /// Medical files still in the live database, unlinked when the patient
+    /// was deleted, and linked to the restored patient again.
+    pub relinked_files: usize,
+    /// Medical files whose content is neither on disk nor in the backup.
+    pub missing_files: Vec<String>,
+}
+
+/// Ids of the copied rows, backup id to live id.
+struct CopiedIds {
+    old_patient: i64,
+    new_patient: u64,
+    consultations: BTreeMap<i64, u64>,
+    /// Also keeps the exam's consultation in the backup.
+    exams: BTreeMap<i64, (u64, i64)>,
+}
+
+struct CopiedPatient {
+    patient_db_id: u64,
+    rows: BTreeMap<String, usize>,
+    /// Checksum and name of each copied or relinked medical file.
+    files: Vec<(String, String)>,
+    relinked_files: usize,
+}
+
+/// Copies rows from the scratch schema into the live one, letting MySQL
+/// assign new ids.
+struct RowCopier<'a> {
+    scratch_schema: &'a str,
+    rows: BTreeMap<String, usize>,
+}
+
+impl RowCopier<'_> {
+    /// Columns present in both the live and the backup table, minus `id`, so
+    /// backups taken before a migration still import.
+    async fn shared_columns(
+        &self,
+        conn: &mut MySqlConnection,
+        table: &str,
+    ) -> Result<Vec<String>, String> {
+        let query = "SELECT CAST(column_name AS CHAR) FROM information_schema.columns
+                     WHERE table_schema = COALESCE(?, DATABASE()) AND table_name = ?
+                     ORDER BY ordinal_position";
+        let live: Vec<String> = sqlx::query_scalar(query)
+            .bind(None::<&str>)
+            .bind(table)
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to read columns of {}: {}", table, e))?;
+        let backup: Vec<String> = sqlx::query_scalar(query)
+            .bind(Some(self.scratch_schema))
+            .bind(table)
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to read columns of {}: {}", table, e))?;
+
+        Ok(live
+            .into_iter()
+            .filter(|column| column != "id" && backup.contains(column))
+            .collect())
+    }
+
+    async fn ids(
+        &self,
+        conn: &mut MySqlConnection,
+        table: &str,
+        key: &str,
+        value: i64,
+    ) -> Result<Vec<i64>, String> {
+        sqlx::query_scalar(&format!(
+            "SELECT CAST(id AS SIGNED) FROM {}.{} WHERE {} = ? ORDER BY id",
+            quote_identifier(self.scratch_schema),
+            quote_identifier(table),
+            quote_identifier(key)
+        ))
+        .bind(value)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to read {} from backup: {}", table, e))
+    }
+
+    /// Copies one backup row, replacing the listed columns with SQL
+    /// expressions over the source row `src`. Returns the new id.
+    async fn copy_row(
+        &mut self,
+        conn: &mut MySqlConnection,
+        table: &str,
+        columns: &[String],
+        id: i64,
+        overrides: &[(&str, String)],
+    ) -> Result<u64, String> {
+        let values: Vec<String> = columns
+            .iter()
+            .map(|column| {
+                overrides
+                    .iter()
+                    .find(|(name, _)| name == column)
+                    .map(|(_, expression)| expression.clone())
+                    .unwrap_or_else(|| format!("src.{}", quote_identifier(column)))
+            })
+            .collect();
+        let names: Vec<String> = columns.iter().map(|c| quote_identifier(c)).collect();
+
+        let result = sqlx::query(&format!(
+            "INSERT INTO {} ({}) SELECT {} FROM {}.{} AS src WHERE src.id = ?",
+            quote_identifier(table),
+            names.join(", "),
+            values.join(", "),
+            quote_identifier(self.scratch_schema),
+            quote_identifier(table)
+        ))
+        .bind(id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to restore {} #{}: {}", table, id, e))?;
+
+        *self.rows.entry(table.to_string()).or_default() += 1;
+        Ok(result.last_insert_id())
+    }
+
+    /// Copies every row of `table` whose `key` is `old_parent`, pointing it at `new_parent`.
+    async fn copy_children(
+        &mut self,
+        conn: &mut MySqlConnection,
+        table: &str,
+        key: &str,
+        old_parent: i64,
+        new_parent: u64,
+        extra: &[(&str, String)],
+    ) -> Result<Vec<(i64, u64)>, String> {
+        let columns = self.shared_columns(conn, table).await?;
+        let mut overrides = vec![(key, new_parent.to_string())];
+        overrides.extend(extra.iter().cloned());
+
+        let mut copied = Vec::new();
+        for id in self.ids(conn, table, key, old_parent).await? {
+            let new_id = self.copy_row(conn, table, &columns, id, &overrides).await?;
+            copied.push((id, new_id));
+        }
+        Ok(copied)
+    }
+}
+
+/// Re-imports one patient and everything attached to them from a backup,
+/// without touching anyone else's data. The backup is loaded into a scratch
+/// schema and the rows are copied over in a single transaction with fresh
+/// ids. Fails if a patient with the same `patient_id` still exists.
+/// Medical files come along; content no longer on disk is taken from the
+/// backup when it is a full one.
+#[command]
+pub async fn restore_patient_from_backup(
+    app: AppHandle,
+    db: State<'_, DbConfigState>,
+    filename: String,
+    patient_id: String,
+    passphrase: Option<String>,
+    engine: Option<BackupEngine>,
+) -> Result<PatientRestoreReport, BackupError> {
+    let config = db.current()?;
+    let scratch = scratch::config(&config, SCRATCH_SUFFIX);
+
+    let backup_path = safe_path::resolve(&location::backup_dir(&app)?, &filename)?;
+    if !backup_path.exists() {
+        return Err(BackupError::NotFound(filename));
+    }
+
+    let mut conn = db_config::connect(&config).await?;
+    let existing: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM patients WHERE patient_id = ?")
+        .bind(&patient_id)
+        .fetch_one(&mut conn)
+        .await
+        .map_err(|e| format!("Failed to look up patient: {}", e))?;
+    if existing > 0 {
+        return Err(format!("Patient {} already exists in the live database", patient_id).into());
+    }
+
+    let job = app.state::<JobManager>().start(JobKind::Restore)?;
+    let options = RestoreOptions {
+        passphrase: passphrase.clone(),
+        engine: engine.unwrap_or_default(),
+        attachments: false,
+        drop_tables: Vec::new(),
+        job: job.token.clone(),
+    };
+    job.finish(
+        scratch::restore_into(&app, &mut conn, &scratch, &filename, &backup_path, options)
+            .await
+            .map_err(BackupError::from),
+    )?;
+
+    let result = copy_patient(&mut conn, &scratch.database, &patient_id).await;
+    scratch::drop_schema(&mut conn, &scratch.database).await?;
+    let copied = result?;
+
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app directory: {}", e))?;
+    let files = copied.files;
+    let missing_files = tauri::async_runtime::spawn_blocking(move || {
+        restore_file_contents(
+            &app_dir,
+            &filename,
+            &backup_path,
+            passphrase.as_deref(),
+            &files,
+        )
+    })
+    .await
+    .map_err(|e| format!("Restore task failed: {}", e))?;
+
+    Ok(PatientRestoreReport {
+        patient_id,
+        patient_db_id: copied.patient_db_id,
+        rows: copied.rows,
+        relinked_files: copied.relinked_files,
+        missing_files,
+    })
+}
+
+/// The content of the copied files may have been deleted along with the
+/// patient. Full backups carry it, so whatever is absent is extracted from
+/// the backup. Returns the names of the files still without content.
+fn restore_file_contents(
+    app_dir: &Path,
+    filename: &str,
+    backup_path: &Path,
+    passphrase: Option<&str>,
+    files: &[(String, String)],
+) -> Vec<String> {
+    // A malformed checksum names no file, so it is reported as missing
+    let is_absent = |sha256: &str| {
+        !file_storage::relative_path(sha256)
+            .and_then(|relative| safe_path::resolve(app_dir, &relative))
+            .is_ok_and(|path| path.is_file())
+    };
+    let absent: BTreeSet<String> = files
+        .iter()
+        .filter(|(sha256, _)| is_absent(sha256))
+        .filter_map(|(sha256, _)| file_storage::relative_path(sha256).ok())
+        .collect();
+    if !absent.is_empty() {
+        if let Err(e) =
+            super::restore_attachments(app_dir, filename, backup_path, passphrase, &absent)
+        {
+            log::warn!("Failed to restore medical files from {}: {}", filename, e);
+        }
+    }
+    files
+        .iter()
+        .filter(|(sha256, _)| is_absent(sha256))
+        .map(|(_, name)| name.clone())
+        .collect()
+}
+
+async fn copy_patient(
+    conn: &mut MySqlConnection,
+    scratch_schema: &str,
+    patient_id: &str,
+) -> Result<CopiedPatient, String> {
+    let schema = quote_identifier(scratch_schema);
+
+    let old_patient: i64 = sqlx::query_scalar(&format!(
+        "SELECT CAST(id AS SIGNED) FROM {}.patients WHERE patient_id = ?",
+        schema
+    ))
+    .bind(patient_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to read patient from backup: {}", e))?
+    .ok_or_else(|| format!("Patient {} is not in this backup", patient_id))?;
+
+    // consultations.doctor_db_id is mandatory, so its doctors must still exist
+    let missing_doctors: Vec<i64> = sqlx::query_scalar(&format!(
+        "SELECT DISTINCT CAST(c.doctor_db_id AS SIGNED) FROM {}.consultations c
+         LEFT JOIN users u ON u.id = c.doctor_db_id
+         WHERE c.patient_db_id = ? AND u.id IS NULL",
+        schema
+    ))
+    .bind(old_patient)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to check consultation doctors: {}", e))?;
+    if !missing_doctors.is_empty() {
+        return Err(format!(
+            "Consultations reference doctors that no longer exist (user ids {:?})",
+            missing_doctors
+        ));
+    }
+
+    let mut tx = conn
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let mut copier = RowCopier {
+        scratch_schema,
+        rows: BTreeMap::new(),
+    };
+
+    let columns = copier.shared_columns(&mut tx, "patients").await?;
+    let new_patient = copier
+        .copy_row(&mut tx, "patients", &columns, old_patient, &[])
+        .await?;
+
+    // Appointments may name a doctor who has since been removed; that column is nullable
+    let appointment_doctor = [(
+        "doctor_db_id",
+        "(SELECT u.id FROM users u WHERE u.id = src.doctor_db_id)".to_string(),
+    )];
+    for table in PATIENT_TABLES {
+        let extra: &[(&str, String)] = if table == "appointments" {
+            &appointment_doctor
+        } else {
+            &[]
+        };
+        copier
+            .copy_children(
+                &mut tx,
+                table,
+                "patient_db_id",
+                old_patient,
+                new_patient,
+                extra,
+            )
+            .await?;
+    }
+
+    let consultations = copier
+        .copy_children(
+            &mut tx,
+            "consultations",
+            "patient_db_id",
+            old_patient,
+            new_patient,
+            &[],
+        )
+        .await?;
+    let mut ids = CopiedIds {
+        old_patient,
+        new_patient,
+        consultations: BTreeMap::new(),
+        exams: BTreeMap::new(),
+    };
+    for (old_consultation, new_consultation) in consultations {
+        ids.consultations.insert(old_consultation, new_consultation);
+        for table in CONSULTATION_TABLES {
+            let copied = copier
+                .copy_children(
+                    &mut tx,
+                    table,
+                    "consultation_id",
+                    old_consultation,
+                    new_consultation,
+                    &[],
+                )
+                .await?;
+            if table == "ecg_ett_exams" {
+                ids.exams.extend(
+                    copied
+                        .into_iter()
+                        .map(|(old_exam, new_exam)| (old_exam, (new_exam, old_consultation))),
+                );
+            }
+        }
+    }
+    let (files, relinked_files) = copy_medical_files(&mut tx, &mut copier, &ids).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to commit patient restore: {}", e))?;
+    Ok(CopiedPatient {
+        patient_db_id: new_patient,
+        rows: copier.rows,
+        files,
+        relinked_files,
+    })
+}
+
+fn sql_id(id: Option<u64>) -> String {
+    id.map_or_else(|| "NULL".to_string(), |id| id.to_string())
+}
+
+/// Numeric entries of an exam's file list; the rest are legacy paths.
+fn listed_file_ids(json: &str) -> Vec<i64> {
+    serde_json::from_str::<Vec<Value>>(json)
+        .map(|entries| entries.iter().filter_map(Value::as_i64).collect())
+        .unwrap_or_default()
+}
+
+/// Points an exam's file list at the copied files. Ids that were not copied
+/// are dropped, as they could name another patient's file in the live
+/// database; legacy paths are kept.
+fn remap_file_list(json: &str, files: &BTreeMap<i64, u64>) -> String {
+    let Ok(entries) = serde_json::from_str::<Vec<Value>>(json) else {
+        return json.to_string();
+    };
+    let entries: Vec<Value> = entries
+        .into_iter()
+        .filter_map(|entry| match entry.as_i64() {
+            Some(id) => files.get(&id).map(|&new_id| Value::from(new_id)),
+            None => Some(entry),
+        })
+        .collect();
+    Value::Array(entries).to_string()
+}
+
+/// Of the live rows with a file's content and no links, the one to link
+/// back: preferably with the same name and kind, else the oldest. `taken`
+/// holds rows already linked back for other files.
+fn pick_unlinked(
+    candidates: &[(u64, String, Option<String>)],
+    name: &str,
+    kind: Option<&str>,
+    taken: &BTreeSet<u64>,
+) -> Option<u64> {
+    candidates
+        .iter()
+        .filter(|(id, _, _)| !taken.contains(id))
+        .max_by_key(|(id, n, k)| (n == name, k.as_deref() == kind, Reverse(*id)))
+        .map(|(id, _, _)| *id)
+}
+
+/// Live row for backup file `id` left behind when its patient was deleted:
+/// the `medical_files` foreign keys only unlink rows.
+async fn find_unlinked(
+    conn: &mut MySqlConnection,
+    scratch_schema: &str,
+    id: i64,
+    taken: &BTreeSet<u64>,
+) -> Result<Option<u64>, String> {
+    let (sha256, name, kind): (String, String, Option<String>) = sqlx::query_as(&format!(
+        "SELECT sha256, original_name, kind FROM {}.medical_files WHERE id = ?",
+        quote_identifier(scratch_schema)
+    ))
+    .bind(id)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to read medical_files from backup: {}", e))?;
+    let candidates: Vec<(u64, String, Option<String>)> = sqlx::query_as(
+        "SELECT CAST(id AS UNSIGNED), original_name, kind FROM medical_files
+         WHERE sha256 = ? AND patient_id IS NULL AND consultation_id IS NULL
+           AND exam_id IS NULL",
+    )
+    .bind(&sha256)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to look up medical file {}: {}", sha256, e))?;
+    Ok(pick_unlinked(&candidates, &name, kind.as_deref(), taken))
+}
+
+/// Links the patient's `medical_files` rows back, with their patient,
+/// consultation and exam remapped, then rewrites the copied exams' file
+/// lists. Rows still in the live database without links are reused; the
+/// others are copied from the backup. Backups from before files were linked
+/// to patients only list them in the exams, so those come along too.
+/// Returns the checksum and name of each file, and how many were relinked.
+async fn copy_medical_files(
+    conn: &mut MySqlConnection,
+    copier: &mut RowCopier<'_>,
+    ids: &CopiedIds,
+) -> Result<(Vec<(String, String)>, usize), String> {
+    let mut columns = copier.shared_columns(conn, "medical_files").await?;
+    // The backup predates medical_files
+    if columns.is_empty() {
+        return Ok((Vec::new(), 0));
+    }
+    let schema = quote_identifier(copier.scratch_schema);
+
+    // Backup file id -> the consultation and exam it belongs to in the backup
+    let mut files: BTreeMap<i64, (Option<i64>, Option<i64>)> = BTreeMap::new();
+    if columns.iter().any(|column| column == "patient_id") {
+        let linked: Vec<(i64, Option<i64>, Option<i64>)> = sqlx::query_as(&format!(
+            "SELECT CAST(id AS SIGNED), CAST(consultation_id AS SIGNED),
+                    CAST(exam_id AS SIGNED)
+             FROM {}.medical_files WHERE patient_id = ?",
+            schema
+        ))
+        .bind(ids.old_patient)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to read medical_files from backup: {}", e))?;
+        files.extend(
+            linked
+                .into_iter()
+                .map(|(id, consultation, exam)| (id, (consultation, exam))),
+        );
+    }
+
+    let stored: BTreeSet<i64> = sqlx::query_scalar(&format!(
+        "SELECT CAST(id AS SIGNED) FROM {}.medical_files",
+        schema
+    ))
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to read medical_files from backup: {}", e))?
+    .into_iter()
+    .collect();
+    for (&old_exam, &(_, old_consultation)) in &ids.exams {
+        let (ecg_files, ett_files): (Option<String>, Option<String>) = sqlx::query_as(&format!(
+            "SELECT ecg_files, ett_files FROM {}.ecg_ett_exams WHERE id = ?",
+            schema
+        ))
+        .bind(old_exam)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to read ecg_ett_exams from backup: {}", e))?;
+        for id in [ecg_files, ett_files]
+            .iter()
+            .flatten()
+            .flat_map(|json| listed_file_ids(json))
+            .filter(|id| stored.contains(id))
+        {
+            files
+                .entry(id)
+                .or_insert((Some(old_consultation), Some(old_exam)));
+        }
+    }
+
+    // Rows from before the column may point at content stored in plain
+    let encrypted = if columns.iter().any(|c| c == "encrypted") {
+        "src.encrypted"
+    } else {
+        "FALSE"
+    };
+    for column in ["patient_id", "consultation_id", "exam_id", "encrypted"] {
+        if !columns.iter().any(|c| c == column) {
+            columns.push(column.to_string());
+        }
+    }
+    let mut copied = BTreeMap::new();
+    let mut relinked = BTreeSet::new();
+    for (&id, &(consultation, exam)) in &files {
+        let new_consultation = consultation.and_then(|c| ids.consultations.get(&c).copied());
+        let new_exam = exam
+            .and_then(|e| ids.exams.get(&e))
+            .map(|&(new_exam, _)| new_exam);
+
+        if let Some(live) = find_unlinked(conn, copier.scratch_schema, id, &relinked).await? {
+            sqlx::query(
+                "UPDATE medical_files SET patient_id = ?, consultation_id = ?, exam_id = ?
+                 WHERE id = ?",
+            )
+            .bind(ids.new_patient)
+            .bind(new_consultation)
+            .bind(new_exam)
+            .bind(live)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to relink medical file {}: {}", live, e))?;
+            relinked.insert(live);
+            copied.insert(id, live);
+            continue;
+        }
+
+        let overrides = [
+            ("patient_id", ids.new_patient.to_string()),
+            ("consultation_id", sql_id(new_consultation)),
+            ("exam_id", sql_id(new_exam)),
+            ("encrypted", encrypted.to_string()),
+            (
+                "uploaded_by",
+                "(SELECT u.id FROM users u WHERE u.id = src.uploaded_by)".to_string(),
+            ),
+        ];
+        let new_id = copier
+            .copy_row(conn, "medical_files", &columns, id, &overrides)
+            .await?;
+        copied.insert(id, new_id);
+    }
+
+    for &(new_exam, _) in ids.exams.values() {
+        let (ecg_files, ett_files): (Option<String>, Option<String>) =
+            sqlx::query_as("SELECT ecg_files, ett_files FROM ecg_ett_exams WHERE id = ?")
+                .bind(new_exam)
+                .fetch_one(&mut *conn)
+                .await
+                .map_err(|e| format!("Failed to read exam {}: {}", new_exam, e))?;
+        sqlx::query("UPDATE ecg_ett_exams SET ecg_files = ?, ett_files = ? WHERE id = ?")
+            .bind(ecg_files.map(|json| remap_file_list(&json, &copied)))
+            .bind(ett_files.map(|json| remap_file_list(&json, &copied)))
+            .bind(new_exam)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to update exam {}: {}", new_exam, e))?;
+    }
+
+    let mut stored_files = Vec::new();
+    for &new_id in copied.values() {
+        let file: (String, String) =
+            sqlx::query_as("SELECT sha256, original_name FROM medical_files WHERE id = ?")
+                .bind(new_id)
+                .fetch_one(&mut *conn)
+                .await
+                .map_err(|e| format!("Failed to read file {}: {}", new_id, e))?;
+        stored_files.push(file);
+    }
+    Ok((stored_files, relinked.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_file_ids_from_exam_lists() {
+        assert_eq!(
+            listed_file_ids(r#"[3, "medical_files/20240115_093012_a.pdf", 7]"#),
+            vec![3, 7]
+        );
+        assert!(listed_file_ids("not json").is_empty());
+    }
+
+    #[test]
+    fn relinks_the_best_matching_unlinked_file() {
+        let candidates = vec![
+            (4, "scan.pdf".to_string(), Some("ECG".to_string())),
+            (5, "ecg.pdf".to_string(), Some("ETT".to_string())),
+            (6, "ecg.pdf".to_string(), Some("ECG".to_string())),
+            (7, "ecg.pdf".to_string(), Some("ECG".to_string())),
+        ];
+        let none = BTreeSet::new();
+        assert_eq!(
+            pick_unlinked(&candidates, "ecg.pdf", Some("ECG"), &none),
+            Some(6)
+        );
+        assert_eq!(
+            pick_unlinked(&candidates, "other.pdf", None, &none),
+            Some(4)
+        );
+
+        // Each live row is linked back to one file only
+        let taken = BTreeSet::from([6, 7]);
+        assert_eq!(
+            pick_unlinked(&candidates, "ecg.pdf", Some("ECG"), &taken),
+            Some(5)
+        );
+        let taken = BTreeSet::from([4, 5, 6, 7]);
+        assert_eq!(
+            pick_unlinked(&candidates, "ecg.pdf", Some("ECG"), &taken),
+            None
+        );
+        assert_eq!(pick_unlinked(&[], "ecg.pdf", Some("ECG"), &none), None);
+    }
+
+    #[test]
+    fn remaps_exam_lists_to_copied_files() {
+        let copied = BTreeMap::from([(3, 103), (7, 107)]);
+        assert_eq!(
+            remap_file_list(r#"[3,"medical_files/a.pdf",7,9]"#, &copied),
+            r#"[103,"medical_files/a.pdf",107]"#
+        );
+        assert_eq!(remap_file_list("not json", &copied), "not json");
+    }
+}
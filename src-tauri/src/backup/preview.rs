@@ -0,0 +1,264 @@
+use chrono::Local;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{command, AppHandle, Manager, State};
+
+use super::job::{Job, JobKind, JobManager};
+use super::{
+    location, manifest, restore_with_snapshot, restored_message, scratch, BackupEngine,
+    BackupError, RestoreOptions,
+};
+use crate::db_config::{self, DbConfigState};
+use crate::safe_path;
+
+const SCRATCH_SUFFIX: &str = "_restore_preview";
+
+/// A previewed restore waiting for `confirm_database_restore`. Held in memory
+/// only, so a restart discards it.
+struct PendingRestore {
+    preview_id: String,
+    filename: String,
+    checksum: String,
+    passphrase: Option<String>,
+    engine: BackupEngine,
+    backup_migration_version: Option<i64>,
+    backup_tables: BTreeSet<String>,
+}
+
+#[derive(Default)]
+pub struct RestorePreviewState(Mutex<Option<PendingRestore>>);
+
+#[derive(Debug, Serialize)]
+pub struct TableDiff {
+    pub table: String,
+    /// `None` when the table does not exist on that side.
+    pub live_rows: Option<i64>,
+    pub backup_rows: Option<i64>,
+    pub difference: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestorePreview {
+    pub preview_id: String,
+    pub filename: String,
+    pub live_migration_version: Option<i64>,
+    pub backup_migration_version: Option<i64>,
+    pub tables: Vec<TableDiff>,
+}
+
+fn diff_tables(
+    live: &manifest::DatabaseSummary,
+    backup: &manifest::DatabaseSummary,
+) -> Vec<TableDiff> {
+    let names: BTreeSet<&String> = live.tables.keys().chain(backup.tables.keys()).collect();
+    names
+        .into_iter()
+        .map(|table| {
+            let live_rows = live.tables.get(table).copied();
+            let backup_rows = backup.tables.get(table).copied();
+            TableDiff {
+                table: table.clone(),
+                live_rows,
+                backup_rows,
+                difference: backup_rows.unwrap_or(0) - live_rows.unwrap_or(0),
+            }
+        })
+        .collect()
+}
+
+/// Restores a backup into a scratch schema and compares its row counts with
+/// the live database. Nothing is written to the live database; call
+/// `confirm_database_restore` with the returned id to apply it.
+#[command]
+pub async fn preview_database_restore(
+    app: AppHandle,
+    db: State<'_, DbConfigState>,
+    preview: State<'_, RestorePreviewState>,
+    filename: String,
+    passphrase: Option<String>,
+    engine: Option<BackupEngine>,
+) -> Result<RestorePreview, BackupError> {
+    let config = db.current()?;
+    let engine = engine.unwrap_or_default();
+    let scratch = scratch::config(&config, SCRATCH_SUFFIX);
+
+    let backup_path = safe_path::resolve(&location::backup_dir(&app)?, &filename)?;
+    if !backup_path.exists() {
+        return Err(BackupError::NotFound(filename));
+    }
+
+    let job = app.state::<JobManager>().start(JobKind::Restore)?;
+    let mut conn = db_config::connect(&config).await?;
+    let options = RestoreOptions {
+        passphrase: passphrase.clone(),
+        engine,
+        attachments: false,
+        drop_tables: Vec::new(),
+        job: job.token.clone(),
+    };
+    let checksum = job.finish(
+        scratch::restore_into(&app, &mut conn, &scratch, &filename, &backup_path, options)
+            .await
+            .map_err(BackupError::from),
+    )?;
+
+    let backup = async {
+        let mut scratch_conn = db_config::connect(&scratch).await?;
+        manifest::summarize_database(&mut scratch_conn).await
+    }
+    .await;
+    let live = manifest::summarize_database(&mut conn).await;
+    let summaries = backup.and_then(|backup| live.map(|live| (checksum, live, backup)));
+
+    // The scratch copy is only needed for counting; confirmation replays the file
+    scratch::drop_schema(&mut conn, &scratch.database).await?;
+    let (checksum, live, backup) = summaries?;
+
+    let preview_id = format!("{}", Local::now().timestamp_micros());
+    *preview
+        .0
+        .lock()
+        .map_err(|_| "Restore preview lock poisoned".to_string())? = Some(PendingRestore {
+        preview_id: preview_id.clone(),
+        filename: filename.clone(),
+        checksum,
+        passphrase,
+        engine,
+        backup_migration_version: backup.migration_version,
+        backup_tables: backup.tables.keys().cloned().collect(),
+    });
+
+    Ok(RestorePreview {
+        preview_id,
+        filename,
+        live_migration_version: live.migration_version,
+        backup_migration_version: backup.migration_version,
+        tables: diff_tables(&live, &backup),
+    })
+}
+
+/// Live tables the backup does not have, when it was taken at an older
+/// migration than the live database. Without `allow_older_schema` that is an
+/// error, since restoring it means dropping those tables.
+async fn tables_to_drop(
+    config: &db_config::DbConfig,
+    pending: &PendingRestore,
+    allow_older_schema: bool,
+) -> Result<Vec<String>, BackupError> {
+    let mut conn = db_config::connect(config).await?;
+    let live = manifest::migration_version(&mut conn).await;
+    let older = match (pending.backup_migration_version, live) {
+        (Some(backup), Some(live)) => backup < live,
+        (None, Some(_)) => true,
+        (_, None) => false,
+    };
+    if !older {
+        return Ok(Vec::new());
+    }
+    if !allow_older_schema {
+        return Err(BackupError::OlderSchema {
+            backup: pending.backup_migration_version,
+            live,
+        });
+    }
+    Ok(manifest::list_tables(&mut conn)
+        .await?
+        .into_iter()
+        .filter(|table| !pending.backup_tables.contains(table))
+        .collect())
+}
+
+/// Claims the job slot and checks the backup file is the one that was
+/// previewed. Returns the job, the file and the live tables to drop.
+async fn prepare_restore(
+    app: &AppHandle,
+    config: &db_config::DbConfig,
+    pending: &PendingRestore,
+    allow_older_schema: bool,
+) -> Result<(Job, PathBuf, Vec<String>), BackupError> {
+    let drop_tables = tables_to_drop(config, pending, allow_older_schema).await?;
+    let backup_path = safe_path::resolve(&location::backup_dir(app)?, &pending.filename)?;
+    let job = app.state::<JobManager>().start(JobKind::Restore)?;
+
+    let checksum = {
+        let backup_path = backup_path.clone();
+        tauri::async_runtime::spawn_blocking(move || manifest::sha256_file(&backup_path))
+            .await
+            .map_err(|e| format!("Restore task failed: {}", e))?
+            .map_err(|e| format!("Failed to hash backup file: {}", e))?
+    };
+    if checksum != pending.checksum {
+        return Err(format!(
+            "{} changed since it was previewed; preview it again",
+            pending.filename
+        )
+        .into());
+    }
+    Ok((job, backup_path, drop_tables))
+}
+
+/// Replays a previewed backup into the live database. The file must be
+/// unchanged since the preview. A backup older than the live schema is
+/// refused unless `allow_older_schema` is set.
+#[command]
+pub async fn confirm_database_restore(
+    app: AppHandle,
+    db: State<'_, DbConfigState>,
+    preview: State<'_, RestorePreviewState>,
+    preview_id: String,
+    allow_older_schema: Option<bool>,
+) -> Result<String, BackupError> {
+    let config = db.current()?;
+    let pending = {
+        let mut pending = preview
+            .0
+            .lock()
+            .map_err(|_| "Restore preview lock poisoned".to_string())?;
+        match pending.take() {
+            Some(p) if p.preview_id == preview_id => p,
+            other => {
+                *pending = other;
+                return Err("No matching restore preview; preview the backup again"
+                    .to_string()
+                    .into());
+            }
+        }
+    };
+
+    let (job, backup_path, drop_tables) =
+        match prepare_restore(&app, &config, &pending, allow_older_schema.unwrap_or(false)).await {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                // Nothing was restored, so the preview can be confirmed again,
+                // with the override or once the running job is done
+                if let Ok(mut slot) = preview.0.lock() {
+                    slot.get_or_insert(pending);
+                }
+                return Err(e);
+            }
+        };
+
+    let filename = pending.filename.clone();
+    let options = RestoreOptions {
+        passphrase: pending.passphrase,
+        engine: pending.engine,
+        attachments: true,
+        drop_tables,
+        job: job.token.clone(),
+    };
+    let attachments =
+        job.finish(restore_with_snapshot(&app, &config, &filename, backup_path, options).await)?;
+
+    Ok(restored_message(&filename, attachments))
+}
+
+#[command]
+pub fn cancel_database_restore(preview: State<'_, RestorePreviewState>) -> Result<(), BackupError> {
+    *preview
+        .0
+        .lock()
+        .map_err(|_| "Restore preview lock poisoned".to_string())? = None;
+    Ok(())
+}
@@ -0,0 +1,304 @@
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeDelta, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{command, AppHandle};
+
+use super::{parse_backup_name, remove_backup, BackupError};
+use crate::config_file;
+
+const RETENTION_FILE: &str = "backup_retention.json";
+
+/// Grandfather-father-son retention. A zero disables that tier; a policy of
+/// all zeros keeps every backup.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    #[serde(default)]
+    pub keep_last: u32,
+    #[serde(default)]
+    pub keep_daily_days: u32,
+    #[serde(default)]
+    pub keep_weekly_weeks: u32,
+    #[serde(default)]
+    pub keep_monthly_months: u32,
+}
+
+impl RetentionPolicy {
+    fn is_disabled(&self) -> bool {
+        self.keep_last == 0
+            && self.keep_daily_days == 0
+            && self.keep_weekly_weeks == 0
+            && self.keep_monthly_months == 0
+    }
+}
+
+/// Calendar period a backup falls into for one retention tier.
+type Bucket = fn(&DateTime<Local>) -> (i32, u32);
+
+struct BackupFile {
+    filename: String,
+    path: PathBuf,
+    created_at: DateTime<Local>,
+}
+
+fn load_policy(app: &AppHandle) -> Result<RetentionPolicy, String> {
+    Ok(config_file::load(app, RETENTION_FILE)?.unwrap_or_default())
+}
+
+/// Backup time from the `_backup_YYYYMMDD_HHMMSS` part of the name, falling
+/// back to the file's modification time for renamed files.
+pub fn backup_time(filename: &str, path: &Path) -> Option<DateTime<Local>> {
+    filename
+        .find("_backup_")
+        .and_then(|start| filename.get(start + 8..start + 23))
+        .and_then(|stamp| NaiveDateTime::parse_from_str(stamp, "%Y%m%d_%H%M%S").ok())
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        .or_else(|| {
+            fs::metadata(path)
+                .and_then(|m| m.modified())
+                .ok()
+                .map(DateTime::<Local>::from)
+        })
+}
+
+fn backup_files(backup_dir: &Path) -> Result<Vec<BackupFile>, String> {
+    if !backup_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let entries =
+        fs::read_dir(backup_dir).map_err(|e| format!("Failed to read backup directory: {}", e))?;
+
+    let mut files: Vec<BackupFile> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let filename = entry.file_name().to_str()?.to_string();
+            parse_backup_name(&filename)?;
+            let path = entry.path();
+            let created_at = backup_time(&filename, &path)?;
+            Some(BackupFile {
+                filename,
+                path,
+                created_at,
+            })
+        })
+        .collect();
+
+    files.sort_by_key(|file| Reverse(file.created_at));
+    Ok(files)
+}
+
+/// Returns the files the policy would delete. `files` must be sorted newest first.
+fn select_expired<'a>(
+    files: &'a [BackupFile],
+    policy: &RetentionPolicy,
+    now: DateTime<Local>,
+) -> Vec<&'a BackupFile> {
+    if policy.is_disabled() || files.is_empty() {
+        return Vec::new();
+    }
+
+    let mut keep = vec![false; files.len()];
+    // The newest backup is never pruned, whatever the policy says
+    keep[0] = true;
+    for flag in keep.iter_mut().take(policy.keep_last as usize) {
+        *flag = true;
+    }
+
+    let tiers: [(u32, TimeDelta, Bucket); 3] = [
+        (policy.keep_daily_days, TimeDelta::days(1), |t| {
+            (t.year(), t.ordinal())
+        }),
+        (policy.keep_weekly_weeks, TimeDelta::weeks(1), |t| {
+            let week = t.iso_week();
+            (week.year(), week.week())
+        }),
+        (policy.keep_monthly_months, TimeDelta::days(31), |t| {
+            (t.year(), t.month())
+        }),
+    ];
+
+    for (count, period, bucket) in tiers {
+        if count == 0 {
+            continue;
+        }
+        let cutoff = now - period * count as i32;
+        let mut seen = HashSet::new();
+        for (index, file) in files.iter().enumerate() {
+            if file.created_at < cutoff {
+                break;
+            }
+            // Files are newest first, so the first hit per bucket is the one kept
+            if seen.insert(bucket(&file.created_at)) {
+                keep[index] = true;
+            }
+        }
+    }
+
+    files
+        .iter()
+        .zip(keep)
+        .filter(|(_, kept)| !kept)
+        .map(|(file, _)| file)
+        .collect()
+}
+
+/// Prunes `backup_dir` according to the saved policy, returning the deleted filenames.
+pub fn apply(app: &AppHandle, backup_dir: &Path) -> Result<Vec<String>, String> {
+    let policy = load_policy(app)?;
+    let files = backup_files(backup_dir)?;
+
+    let mut deleted = Vec::new();
+    for file in select_expired(&files, &policy, Local::now()) {
+        remove_backup(&file.path)?;
+        deleted.push(file.filename.clone());
+    }
+    Ok(deleted)
+}
+
+#[command]
+pub fn get_retention_policy(app: AppHandle) -> Result<RetentionPolicy, BackupError> {
+    Ok(load_policy(&app)?)
+}
+
+#[command]
+pub fn set_retention_policy(app: AppHandle, policy: RetentionPolicy) -> Result<(), BackupError> {
+    Ok(config_file::save(&app, RETENTION_FILE, &policy)?)
+}
+
+/// Dry run: lists the backups that would be deleted by `policy` (or the saved one).
+#[command]
+pub fn preview_backup_retention(
+    app: AppHandle,
+    policy: Option<RetentionPolicy>,
+) -> Result<Vec<String>, BackupError> {
+    let policy = match policy {
+        Some(policy) => policy,
+        None => load_policy(&app)?,
+    };
+    let files = backup_files(&super::location::backup_dir(&app)?)?;
+    Ok(select_expired(&files, &policy, Local::now())
+        .into_iter()
+        .map(|file| file.filename.clone())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Saturday 15 June 2024, noon.
+    fn now() -> DateTime<Local> {
+        at(6, 15, 12)
+    }
+
+    fn at(month: u32, day: u32, hour: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2024, month, day, hour, 0, 0)
+            .unwrap()
+    }
+
+    /// Backups named after their time, sorted newest first like `backup_files`.
+    fn files(times: &[DateTime<Local>]) -> Vec<BackupFile> {
+        let mut files: Vec<BackupFile> = times
+            .iter()
+            .map(|created_at| {
+                let filename = format!("cardio_backup_{}.sql", created_at.format("%Y%m%d_%H%M%S"));
+                BackupFile {
+                    path: PathBuf::from(&filename),
+                    filename,
+                    created_at: *created_at,
+                }
+            })
+            .collect();
+        files.sort_by_key(|file| Reverse(file.created_at));
+        files
+    }
+
+    fn expired(files: &[BackupFile], policy: &RetentionPolicy) -> Vec<DateTime<Local>> {
+        select_expired(files, policy, now())
+            .into_iter()
+            .map(|file| file.created_at)
+            .collect()
+    }
+
+    #[test]
+    fn keep_last_only_keeps_the_newest_n() {
+        let files = files(&[
+            at(6, 15, 10),
+            at(6, 14, 10),
+            at(6, 13, 10),
+            at(6, 12, 10),
+            at(5, 1, 10),
+        ]);
+        let policy = RetentionPolicy {
+            keep_last: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            expired(&files, &policy),
+            [at(6, 13, 10), at(6, 12, 10), at(5, 1, 10)]
+        );
+    }
+
+    #[test]
+    fn overlapping_tiers_keep_the_newest_backup_of_each_period() {
+        let files = files(&[
+            at(6, 15, 10),
+            at(6, 15, 8),
+            at(6, 14, 20),
+            at(6, 13, 9),
+            at(6, 11, 9),
+            at(6, 5, 9),
+            at(5, 25, 9),
+        ]);
+        let policy = RetentionPolicy {
+            keep_daily_days: 3,
+            keep_weekly_weeks: 2,
+            ..Default::default()
+        };
+        // The 15th 10:00 is both the daily and the weekly pick for its week, so
+        // the 11th is not kept by either tier
+        assert_eq!(
+            expired(&files, &policy),
+            [at(6, 15, 8), at(6, 11, 9), at(5, 25, 9)]
+        );
+    }
+
+    #[test]
+    fn newest_backup_is_always_kept() {
+        let policy = RetentionPolicy {
+            keep_daily_days: 1,
+            ..Default::default()
+        };
+        assert!(expired(&files(&[at(6, 1, 9)]), &policy).is_empty());
+        assert_eq!(
+            expired(&files(&[at(6, 1, 9), at(5, 20, 9)]), &policy),
+            [at(5, 20, 9)]
+        );
+    }
+
+    #[test]
+    fn all_zero_policy_keeps_everything() {
+        let files = files(&[at(6, 15, 10), at(1, 1, 10), at(2, 1, 10)]);
+        assert!(expired(&files, &RetentionPolicy::default()).is_empty());
+        assert!(select_expired(&[], &RetentionPolicy::default(), now()).is_empty());
+    }
+
+    #[test]
+    fn files_past_every_cutoff_are_expired() {
+        let files = files(&[at(3, 20, 9), at(3, 10, 9), at(2, 15, 9), at(1, 5, 9)]);
+        let policy = RetentionPolicy {
+            keep_last: 0,
+            keep_daily_days: 7,
+            keep_weekly_weeks: 4,
+            keep_monthly_months: 2,
+        };
+        assert_eq!(
+            expired(&files, &policy),
+            [at(3, 10, 9), at(2, 15, 9), at(1, 5, 9)]
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use chrono::{DateTime, Datelike, Local, NaiveTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{command, AppHandle, Emitter, Manager, State};
+
+use super::compression::Compression;
+use super::job::{JobKind, JobManager};
+use super::{
+    backup_filename, history, perform_backup, BackupEngine, BackupError, BackupFormat,
+    BackupRequest, BackupType,
+};
+use crate::config_file;
+use crate::db_config::DbConfigState;
+
+const SCHEDULE_FILE: &str = "backup_schedule.json";
+const FAILURE_EVENT: &str = "backup-failed";
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    Daily,
+    Weekly,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupSchedule {
+    pub enabled: bool,
+    pub frequency: Frequency,
+    /// Local time of day, "HH:MM".
+    pub time: String,
+    /// Day of week for weekly backups, 0 = Monday.
+    #[serde(default)]
+    pub weekday: u32,
+    #[serde(default)]
+    pub format: BackupFormat,
+    #[serde(default)]
+    pub compression: Compression,
+    #[serde(default)]
+    pub engine: BackupEngine,
+}
+
+impl Default for BackupSchedule {
+    fn default() -> Self {
+        BackupSchedule {
+            enabled: false,
+            frequency: Frequency::Daily,
+            time: "02:00".to_string(),
+            weekday: 0,
+            format: BackupFormat::Sql,
+            compression: Compression::Gzip,
+            engine: BackupEngine::Auto,
+        }
+    }
+}
+
+impl BackupSchedule {
+    fn validate(&self) -> Result<(), String> {
+        NaiveTime::parse_from_str(&self.time, "%H:%M")
+            .map_err(|_| format!("Invalid backup time '{}', expected HH:MM", self.time))?;
+        if self.weekday > 6 {
+            return Err("Backup weekday must be between 0 (Monday) and 6 (Sunday)".to_string());
+        }
+        Ok(())
+    }
+
+    /// First scheduled instant strictly after `after`.
+    fn next_run_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let time = NaiveTime::parse_from_str(&self.time, "%H:%M").ok()?;
+        (0..=7).find_map(|days| {
+            let date = after.date_naive() + TimeDelta::days(days);
+            if self.frequency == Frequency::Weekly
+                && date.weekday().num_days_from_monday() != self.weekday
+            {
+                return None;
+            }
+            date.and_time(time)
+                .and_local_timezone(Local)
+                .earliest()
+                .filter(|candidate| *candidate > after)
+        })
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct BackupFailure {
+    filename: String,
+    error: String,
+}
+
+pub struct BackupScheduleState(pub Mutex<BackupSchedule>);
+
+fn load(app: &AppHandle) -> BackupSchedule {
+    config_file::load(app, SCHEDULE_FILE)
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+fn current(app: &AppHandle) -> Option<BackupSchedule> {
+    let state = app.state::<BackupScheduleState>();
+    let schedule = state.0.lock().ok()?;
+    Some(schedule.clone())
+}
+
+/// Registers the schedule state and starts the background loop. Missed runs
+/// while the application was closed are not caught up.
+pub fn start(app: &AppHandle) {
+    app.manage(BackupScheduleState(Mutex::new(load(app))));
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut last_check = Local::now();
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let now = Local::now();
+
+            if let Some(schedule) = current(&app).filter(|s| s.enabled) {
+                let due = schedule
+                    .next_run_after(last_check)
+                    .is_some_and(|next| next <= now);
+                if due {
+                    run_scheduled_backup(&app, &schedule).await;
+                }
+            }
+
+            last_check = now;
+        }
+    });
+}
+
+async fn run_scheduled_backup(app: &AppHandle, schedule: &BackupSchedule) {
+    let config = match app.state::<DbConfigState>().current() {
+        Ok(config) => config,
+        Err(e) => {
+            let _ = app.emit(
+                FAILURE_EVENT,
+                BackupFailure {
+                    filename: String::new(),
+                    error: e,
+                },
+            );
+            return;
+        }
+    };
+
+    let filename = backup_filename(
+        &config.database,
+        schedule.format,
+        schedule.compression,
+        false,
+    );
+    // A manual backup or restore in progress makes this run fail rather than wait
+    let job = app.state::<JobManager>().start(JobKind::Backup);
+    let result = match &job {
+        Ok(job) => {
+            perform_backup(
+                app.clone(),
+                config.clone(),
+                BackupRequest {
+                    filename: filename.clone(),
+                    backup_type: BackupType::Automatic,
+                    format: schedule.format,
+                    compression: schedule.compression,
+                    passphrase: None,
+                    engine: schedule.engine,
+                    prune: true,
+                    job: job.token.clone(),
+                },
+            )
+            .await
+        }
+        Err(e) => Err(e.to_string()),
+    };
+
+    let recorded = match &result {
+        Ok(outcome) => {
+            history::record(
+                &config,
+                BackupType::Automatic,
+                &filename,
+                Some(outcome.info.size_bytes),
+                None,
+            )
+            .await
+        }
+        Err(e) => history::record(&config, BackupType::Automatic, &filename, None, Some(e)).await,
+    };
+
+    if let Err(e) = result.map(|_| ()).and(recorded) {
+        log::error!("Scheduled backup failed: {}", e);
+        let _ = app.emit(FAILURE_EVENT, BackupFailure { filename, error: e });
+    }
+}
+
+#[command]
+pub fn get_backup_schedule(
+    state: State<'_, BackupScheduleState>,
+) -> Result<BackupSchedule, BackupError> {
+    state
+        .0
+        .lock()
+        .map(|schedule| schedule.clone())
+        .map_err(|_| "Backup schedule lock poisoned".to_string().into())
+}
+
+#[command]
+pub fn set_backup_schedule(
+    app: AppHandle,
+    state: State<'_, BackupScheduleState>,
+    schedule: BackupSchedule,
+) -> Result<Option<String>, BackupError> {
+    schedule.validate()?;
+
+    config_file::save(&app, SCHEDULE_FILE, &schedule)?;
+
+    let next_run = schedule
+        .enabled
+        .then(|| schedule.next_run_after(Local::now()))
+        .flatten()
+        .map(|next| next.to_rfc3339());
+
+    *state
+        .0
+        .lock()
+        .map_err(|_| "Backup schedule lock poisoned".to_string())? = schedule;
+
+    Ok(next_run)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn schedule(frequency: Frequency, time: &str, weekday: u32) -> BackupSchedule {
+        BackupSchedule {
+            enabled: true,
+            frequency,
+            time: time.to_string(),
+            weekday,
+            ..BackupSchedule::default()
+        }
+    }
+
+    /// June 2024 has no DST change; the 12th is a Wednesday.
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2024, 6, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn daily_runs_later_today_before_the_time() {
+        let daily = schedule(Frequency::Daily, "02:00", 0);
+        assert_eq!(daily.next_run_after(at(12, 1, 30)), Some(at(12, 2, 0)));
+    }
+
+    #[test]
+    fn daily_moves_to_tomorrow_once_the_time_has_passed() {
+        let daily = schedule(Frequency::Daily, "02:00", 0);
+        assert_eq!(daily.next_run_after(at(12, 3, 0)), Some(at(13, 2, 0)));
+        assert_eq!(daily.next_run_after(at(12, 2, 0)), Some(at(13, 2, 0)));
+        assert_eq!(
+            daily.next_run_after(at(30, 23, 59)),
+            Local.with_ymd_and_hms(2024, 7, 1, 2, 0, 0).single()
+        );
+    }
+
+    #[test]
+    fn weekly_on_the_same_weekday_before_the_time() {
+        let wednesday = schedule(Frequency::Weekly, "18:30", 2);
+        assert_eq!(wednesday.next_run_after(at(12, 9, 0)), Some(at(12, 18, 30)));
+    }
+
+    #[test]
+    fn weekly_on_the_same_weekday_after_the_time() {
+        let wednesday = schedule(Frequency::Weekly, "18:30", 2);
+        assert_eq!(
+            wednesday.next_run_after(at(12, 19, 0)),
+            Some(at(19, 18, 30))
+        );
+        assert_eq!(
+            wednesday.next_run_after(at(12, 18, 30)),
+            Some(at(19, 18, 30))
+        );
+    }
+
+    #[test]
+    fn weekly_wraps_into_the_next_week() {
+        let monday = schedule(Frequency::Weekly, "02:00", 0);
+        assert_eq!(monday.next_run_after(at(12, 9, 0)), Some(at(17, 2, 0)));
+        assert_eq!(monday.next_run_after(at(16, 23, 0)), Some(at(17, 2, 0)));
+
+        let sunday = schedule(Frequency::Weekly, "02:00", 6);
+        assert_eq!(sunday.next_run_after(at(12, 9, 0)), Some(at(16, 2, 0)));
+        assert_eq!(sunday.next_run_after(at(16, 3, 0)), Some(at(23, 2, 0)));
+    }
+
+    #[test]
+    fn invalid_time_never_runs() {
+        let broken = schedule(Frequency::Daily, "25:00", 0);
+        assert_eq!(broken.next_run_after(at(12, 0, 0)), None);
+    }
+}
@@ -0,0 +1,98 @@
+use sqlx::mysql::MySqlConnection;
+use std::path::Path;
+use tauri::AppHandle;
+
+use super::{manifest, restore_from_file, RestoreOptions};
+use crate::db_config::DbConfig;
+
+/// MySQL limit on schema names.
+const MAX_SCHEMA_LEN: usize = 64;
+
+/// Connection settings for a throwaway schema next to the live database,
+/// named `<database><suffix>`.
+pub fn config(live: &DbConfig, suffix: &str) -> DbConfig {
+    let mut database = live.database.clone();
+    while database.len() + suffix.len() > MAX_SCHEMA_LEN {
+        database.pop();
+    }
+    DbConfig {
+        database: format!("{}{}", database, suffix),
+        ..live.clone()
+    }
+}
+
+pub async fn drop_schema(conn: &mut MySqlConnection, schema: &str) -> Result<(), String> {
+    sqlx::query(&format!(
+        "DROP DATABASE IF EXISTS {}",
+        manifest::quote_identifier(schema)
+    ))
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to drop {}: {}", schema, e))?;
+    Ok(())
+}
+
+/// Recreates the scratch schema with the live database's character set.
+/// `conn` must be connected to the live database.
+async fn create_schema(conn: &mut MySqlConnection, schema: &str) -> Result<(), String> {
+    let (charset, collation): (String, String) = sqlx::query_as(
+        "SELECT CAST(default_character_set_name AS CHAR), CAST(default_collation_name AS CHAR)
+         FROM information_schema.schemata WHERE schema_name = DATABASE()",
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to read database character set: {}", e))?;
+
+    drop_schema(conn, schema).await?;
+    sqlx::query(&format!(
+        "CREATE DATABASE {} CHARACTER SET {} COLLATE {}",
+        manifest::quote_identifier(schema),
+        charset,
+        collation
+    ))
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to create {}: {}", schema, e))?;
+    Ok(())
+}
+
+/// Restores the dump of a backup (never its attachments) into `scratch`,
+/// returning the checksum of the file that was loaded. Dumps holding `USE`,
+/// `CREATE DATABASE` or `DROP DATABASE` are refused by `restore_from_file`,
+/// so the live database is left untouched. The schema is dropped again if
+/// the restore fails.
+pub async fn restore_into(
+    app: &AppHandle,
+    conn: &mut MySqlConnection,
+    scratch: &DbConfig,
+    filename: &str,
+    backup_path: &Path,
+    options: RestoreOptions,
+) -> Result<String, String> {
+    create_schema(conn, &scratch.database).await?;
+
+    let restored = {
+        let app = app.clone();
+        let scratch = scratch.clone();
+        let filename = filename.to_string();
+        let backup_path = backup_path.to_path_buf();
+        tauri::async_runtime::spawn_blocking(move || {
+            let checksum = manifest::sha256_file(&backup_path)
+                .map_err(|e| format!("Failed to hash backup file: {}", e))?;
+            let options = RestoreOptions {
+                attachments: false,
+                ..options
+            };
+            restore_from_file(&app, &scratch, &filename, &backup_path, &options)?;
+            Ok::<_, String>(checksum)
+        })
+        .await
+        .map_err(|e| format!("Restore task failed: {}", e))
+        .and_then(|result| result)
+    };
+
+    if restored.is_err() {
+        let _ = drop_schema(conn, &scratch.database).await;
+    }
+    restored
+}
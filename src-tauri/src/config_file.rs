@@ -0,0 +1,39 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+/// `name` in the app config directory.
+pub fn path(app: &AppHandle, name: &str) -> Result<PathBuf, String> {
+    let config_dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to get app config directory: {}", e))?;
+    Ok(config_dir.join(name))
+}
+
+/// Reads a JSON settings file; `None` if it has never been saved.
+pub fn load<T: DeserializeOwned>(app: &AppHandle, name: &str) -> Result<Option<T>, String> {
+    let path = path(app, name)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", name, e))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("Invalid {}: {}", name, e))
+}
+
+/// Writes a JSON settings file, creating the config directory if needed.
+pub fn save<T: Serialize>(app: &AppHandle, name: &str, value: &T) -> Result<(), String> {
+    let path = path(app, name)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("Failed to serialize {}: {}", name, e))?;
+    fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", name, e))
+}
@@ -0,0 +1,395 @@
+use age::secrecy::{ExposeSecret, SecretString};
+use age::x25519::Identity;
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, Read, Write};
+use std::iter;
+use std::path::Path;
+use std::sync::RwLock;
+use tauri::AppHandle;
+
+use crate::config_file;
+use crate::secrets::KEYRING_SERVICE;
+
+const KEYRING_USER: &str = "medical-files-key";
+/// Used instead of the OS keyring where there is none, e.g. headless Linux.
+const KEY_FILE: &str = "medical_files.key";
+/// First line of every age file; tells encrypted files from ones written
+/// before encryption was enabled.
+const AGE_MAGIC: &[u8] = b"age-encryption.org/v1";
+const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// Leaked once per load or import, so callers can hold on to it.
+static KEY: RwLock<Option<&'static Identity>> = RwLock::new(None);
+
+/// The per-installation key, created on first use. Blocks on the OS keyring
+/// the first time, so it is loaded on a blocking thread.
+///
+/// Full backups carry the files encrypted, so restoring them on another
+/// installation also needs this key; see `export_key`.
+pub async fn key(app: &AppHandle) -> Result<&'static Identity, String> {
+    if let Some(key) = *KEY.read().map_err(|_| lock_poisoned())? {
+        return Ok(key);
+    }
+    let app = app.clone();
+    let key = tauri::async_runtime::spawn_blocking(move || load_or_create(&app))
+        .await
+        .map_err(|e| format!("Key task failed: {}", e))??;
+    let mut slot = KEY.write().map_err(|_| lock_poisoned())?;
+    Ok(*slot.get_or_insert_with(|| Box::leak(Box::new(key))))
+}
+
+fn lock_poisoned() -> String {
+    "Medical files key lock poisoned".to_string()
+}
+
+fn parse(secret: &str) -> Result<Identity, String> {
+    secret
+        .trim()
+        .parse()
+        .map_err(|e| format!("Invalid medical files key: {}", e))
+}
+
+/// A key file, once written, wins over the keyring so that both can never
+/// hold different keys in use.
+fn load_or_create(app: &AppHandle) -> Result<Identity, String> {
+    let path = config_file::path(app, KEY_FILE)?;
+    if path.exists() {
+        let secret = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read medical files key: {}", e))?;
+        return parse(&secret);
+    }
+
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER).ok();
+    let keyring_available = match entry.as_ref().map(|entry| entry.get_password()) {
+        Some(Ok(secret)) => return parse(&secret),
+        Some(Err(keyring::Error::NoEntry)) => true,
+        // A locked keyring may already hold the key; creating another would
+        // make existing files unreadable
+        Some(Err(keyring::Error::NoStorageAccess(e))) => {
+            return Err(format!("Cannot unlock the system keyring: {}", e));
+        }
+        Some(Err(e)) => {
+            log::warn!("System keyring unavailable, using a key file: {}", e);
+            false
+        }
+        None => false,
+    };
+
+    let identity = Identity::generate();
+    let secret = identity.to_string();
+    let stored = keyring_available
+        && entry.is_some_and(|entry| entry.set_password(secret.expose_secret()).is_ok());
+    if !stored {
+        write_key_file(&path, secret.expose_secret(), false)?;
+    }
+    Ok(identity)
+}
+
+/// Saves over the key where `load_or_create` looks for it: the key file if
+/// there is one, otherwise the keyring, falling back to a new key file.
+fn replace_stored_key(app: &AppHandle, secret: &str) -> Result<(), String> {
+    let path = config_file::path(app, KEY_FILE)?;
+    let in_keyring = !path.exists()
+        && keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+            .is_ok_and(|entry| entry.set_password(secret).is_ok());
+    if in_keyring {
+        return Ok(());
+    }
+    write_key_file(&path, secret, true)
+}
+
+fn write_key_file(path: &Path, secret: &str, replace: bool) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+    let mut options = OpenOptions::new();
+    if replace {
+        options.write(true).create(true).truncate(true);
+    } else {
+        options.write(true).create_new(true);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(secret.as_bytes()))
+        .map_err(|e| format!("Failed to write medical files key: {}", e))
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(AGE_MAGIC)
+}
+
+pub fn encrypt(key: &Identity, data: &[u8]) -> Result<Vec<u8>, String> {
+    let recipient = key.to_public();
+    let encryptor = age::Encryptor::with_recipients(iter::once(&recipient as &dyn age::Recipient))
+        .map_err(|e| format!("Failed to encrypt file: {}", e))?;
+
+    let mut encrypted = Vec::with_capacity(data.len() + 256);
+    let mut writer = encryptor
+        .wrap_output(&mut encrypted)
+        .map_err(|e| format!("Failed to encrypt file: {}", e))?;
+    writer
+        .write_all(data)
+        .and_then(|_| writer.finish())
+        .map_err(|e| format!("Failed to encrypt file: {}", e))?;
+    Ok(encrypted)
+}
+
+/// Whether the file at `path` starts like an age file. Reads the header only.
+pub fn is_encrypted_file(path: &Path) -> bool {
+    let mut header = [0u8; AGE_MAGIC.len()];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|_| is_encrypted(&header))
+}
+
+/// Decrypts a stored file. Plain content is only returned with `allow_plain`,
+/// for files recorded as stored before encryption; otherwise it means the
+/// file was replaced behind the app's back.
+pub fn decrypt(key: &Identity, data: Vec<u8>, allow_plain: bool) -> Result<Vec<u8>, String> {
+    if !is_encrypted(&data) {
+        if allow_plain {
+            return Ok(data);
+        }
+        return Err("Stored file is not encrypted; it may have been replaced".to_string());
+    }
+    let decryptor =
+        age::Decryptor::new(&data[..]).map_err(|e| format!("Failed to decrypt file: {}", e))?;
+    let mut reader = decryptor
+        .decrypt(iter::once(key as &dyn age::Identity))
+        .map_err(|e| format!("Failed to decrypt file: {}", e))?;
+
+    let mut decrypted = Vec::with_capacity(data.len());
+    reader
+        .read_to_end(&mut decrypted)
+        .map_err(|e| format!("Failed to decrypt file: {}", e))?;
+    Ok(decrypted)
+}
+
+fn validate_passphrase(passphrase: &str) -> Result<(), String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!(
+            "Key passphrase must be at least {} characters",
+            MIN_PASSPHRASE_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// The key wrapped in an age file protected by `passphrase` (scrypt), to be
+/// kept apart from the backups it unlocks.
+pub fn export_key(key: &Identity, passphrase: &str) -> Result<Vec<u8>, String> {
+    validate_passphrase(passphrase)?;
+    let encryptor =
+        age::Encryptor::with_user_passphrase(SecretString::from(passphrase.to_string()));
+    let mut exported = Vec::new();
+    let mut writer = encryptor
+        .wrap_output(&mut exported)
+        .map_err(|e| format!("Failed to export key: {}", e))?;
+    writer
+        .write_all(key.to_string().expose_secret().as_bytes())
+        .and_then(|_| writer.finish())
+        .map_err(|e| format!("Failed to export key: {}", e))?;
+    Ok(exported)
+}
+
+/// Reads a key written by `export_key`.
+pub fn read_exported_key(exported: &[u8], passphrase: &str) -> Result<Identity, String> {
+    let decryptor = age::Decryptor::new(exported)
+        .map_err(|_| "Not an exported medical files key".to_string())?;
+    if !decryptor.is_scrypt() {
+        return Err("Not an exported medical files key".to_string());
+    }
+    let identity = age::scrypt::Identity::new(SecretString::from(passphrase.to_string()));
+    let mut reader = decryptor
+        .decrypt(iter::once(&identity as &dyn age::Identity))
+        .map_err(|_| "Wrong passphrase for this key".to_string())?;
+    let mut secret = String::new();
+    reader
+        .read_to_string(&mut secret)
+        .map_err(|_| "Exported key is corrupted".to_string())?;
+    parse(&secret)
+}
+
+/// Makes `key` the installation key, in storage and for this session.
+pub fn install_key(app: &AppHandle, key: Identity) -> Result<(), String> {
+    replace_stored_key(app, key.to_string().expose_secret())?;
+    *KEY.write().map_err(|_| lock_poisoned())? = Some(Box::leak(Box::new(key)));
+    Ok(())
+}
+
+pub fn same_key(a: &Identity, b: &Identity) -> bool {
+    a.to_public().to_string() == b.to_public().to_string()
+}
+
+/// Number of files under `dir` that only `key` can decrypt. Reads the age
+/// headers only.
+pub fn count_encrypted_with(key: &Identity, dir: &Path) -> usize {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| {
+            let path = entry.path();
+            if path.is_dir() {
+                return count_encrypted_with(key, &path);
+            }
+            let opens = File::open(&path)
+                .ok()
+                .and_then(|file| age::Decryptor::new_buffered(BufReader::new(file)).ok())
+                .is_some_and(|decryptor| {
+                    decryptor
+                        .decrypt(iter::once(key as &dyn age::Identity))
+                        .is_ok()
+                });
+            opens as usize
+        })
+        .sum()
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct EncryptionReport {
+    pub encrypted: usize,
+    pub already_encrypted: usize,
+    /// Files that could not be encrypted, with the reason.
+    pub failed: Vec<String>,
+}
+
+/// Encrypts in place every plain file under `dir`, including legacy uploads
+/// stored outside the content-addressed layout.
+pub fn encrypt_tree(key: &Identity, dir: &Path, report: &mut EncryptionReport) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if path.is_dir() {
+            encrypt_tree(key, &path, report);
+            continue;
+        }
+        // Staging files of an upload in progress
+        if name.starts_with(".tmp") {
+            continue;
+        }
+        match encrypt_file(key, &path) {
+            Ok(true) => report.encrypted += 1,
+            Ok(false) => report.already_encrypted += 1,
+            Err(e) => report.failed.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+}
+
+fn encrypt_file(key: &Identity, path: &Path) -> Result<bool, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    if is_encrypted(&data) {
+        return Ok(false);
+    }
+    let encrypted = encrypt(key, &data)?;
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut staged =
+        tempfile::NamedTempFile::new_in(dir).map_err(|e| format!("Failed to write file: {}", e))?;
+    staged
+        .write_all(&encrypted)
+        .and_then(|_| staged.as_file().sync_all())
+        .map_err(|e| format!("Failed to write file: {}", e))?;
+    staged
+        .persist(path)
+        .map_err(|e| format!("Failed to replace file: {}", e.error))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ECG: &[u8] = b"%PDF-1.7\n% ECG 12 derivations, patient 42\n";
+
+    #[test]
+    fn round_trips_through_encrypt_and_decrypt() {
+        let key = Identity::generate();
+        let encrypted = encrypt(&key, ECG).unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.windows(ECG.len()).any(|window| window == ECG));
+        assert_eq!(decrypt(&key, encrypted, false).unwrap(), ECG);
+    }
+
+    #[test]
+    fn decrypt_with_another_key_fails() {
+        let encrypted = encrypt(&Identity::generate(), ECG).unwrap();
+        assert!(decrypt(&Identity::generate(), encrypted, false).is_err());
+    }
+
+    #[test]
+    fn plain_content_only_passes_when_allowed() {
+        let key = Identity::generate();
+        assert_eq!(decrypt(&key, ECG.to_vec(), true).unwrap(), ECG);
+        assert!(decrypt(&key, ECG.to_vec(), false).is_err());
+    }
+
+    #[test]
+    fn exported_key_needs_its_passphrase() {
+        let key = Identity::generate();
+        let exported = export_key(&key, "correct horse battery").unwrap();
+
+        let imported = read_exported_key(&exported, "correct horse battery").unwrap();
+        assert!(same_key(&key, &imported));
+        assert_eq!(
+            read_exported_key(&exported, "wrong horse battery").err(),
+            Some("Wrong passphrase for this key".to_string())
+        );
+        assert_eq!(
+            read_exported_key(&encrypt(&key, ECG).unwrap(), "correct horse battery").err(),
+            Some("Not an exported medical files key".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_short_key_passphrases() {
+        let key = Identity::generate();
+        let short = "x".repeat(MIN_PASSPHRASE_LEN - 1);
+        assert!(export_key(&key, &short).is_err());
+        assert!(validate_passphrase(&"x".repeat(MIN_PASSPHRASE_LEN)).is_ok());
+        // Counted in characters, not bytes
+        assert!(validate_passphrase(&"é".repeat(MIN_PASSPHRASE_LEN - 1)).is_err());
+    }
+
+    #[test]
+    fn encrypt_tree_skips_encrypted_and_staging_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = Identity::generate();
+        let shard = dir.path().join("ab");
+        fs::create_dir_all(&shard).unwrap();
+        let plain = shard.join("plain");
+        let encrypted = shard.join("encrypted");
+        let staging = shard.join(".tmpXYZ");
+        fs::write(&plain, ECG).unwrap();
+        fs::write(&encrypted, encrypt(&key, ECG).unwrap()).unwrap();
+        fs::write(&staging, ECG).unwrap();
+        let already = fs::read(&encrypted).unwrap();
+
+        let mut report = EncryptionReport::default();
+        encrypt_tree(&key, dir.path(), &mut report);
+
+        assert_eq!(report.encrypted, 1);
+        assert_eq!(report.already_encrypted, 1);
+        assert!(report.failed.is_empty());
+        assert_eq!(
+            decrypt(&key, fs::read(&plain).unwrap(), false).unwrap(),
+            ECG
+        );
+        assert!(is_encrypted_file(&plain));
+        assert!(!is_encrypted_file(&staging));
+        assert_eq!(fs::read(&encrypted).unwrap(), already);
+        assert_eq!(fs::read(&staging).unwrap(), ECG);
+        assert_eq!(count_encrypted_with(&key, dir.path()), 2);
+    }
+}
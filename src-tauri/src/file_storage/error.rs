@@ -0,0 +1,69 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+const MIB: f64 = 1024.0 * 1024.0;
+
+/// Error returned by the medical file commands, serialized as
+/// `{ code, message }` like the backup errors.
+#[derive(Debug, Clone)]
+pub enum FileStorageError {
+    /// The content is not one of the accepted types; holds the filename.
+    UnsupportedType(String),
+    /// The upload exceeds the size limit.
+    TooLarge {
+        size: usize,
+        limit: usize,
+    },
+    /// No `medical_files` row has this id.
+    NotFound(u64),
+    Failed(String),
+}
+
+impl FileStorageError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            FileStorageError::UnsupportedType(_) => "unsupported_type",
+            FileStorageError::TooLarge { .. } => "too_large",
+            FileStorageError::NotFound(_) => "not_found",
+            FileStorageError::Failed(_) => "failed",
+        }
+    }
+}
+
+impl fmt::Display for FileStorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileStorageError::UnsupportedType(filename) => write!(
+                f,
+                "Unsupported file type: {} (accepted: PDF, PNG, JPEG, DICOM, SCP-ECG, XML)",
+                filename
+            ),
+            FileStorageError::TooLarge { size, limit } => write!(
+                f,
+                "File is too large: {:.1} MB (limit {:.1} MB)",
+                *size as f64 / MIB,
+                *limit as f64 / MIB
+            ),
+            FileStorageError::NotFound(file_id) => write!(f, "File not found: {}", file_id),
+            FileStorageError::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for FileStorageError {}
+
+impl From<String> for FileStorageError {
+    fn from(message: String) -> Self {
+        FileStorageError::Failed(message)
+    }
+}
+
+impl Serialize for FileStorageError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("FileStorageError", 2)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
@@ -0,0 +1,266 @@
+use age::x25519::Identity;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{Connection, MySqlConnection};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{command, AppHandle, State};
+
+use super::{app_data_dir, crypto, mime, store, FileStorageError};
+use crate::db_config::{self, DbConfigState};
+use crate::safe_path;
+
+/// Exams listed uploads by path, relative to the app data directory, before
+/// `medical_files` existed.
+const LEGACY_PREFIX: &str = "medical_files/";
+/// Legacy uploads were saved as `YYYYMMDD_HHMMSS_<original name>`.
+const LEGACY_STAMP_LEN: usize = 16;
+
+#[derive(Debug, Default, Serialize)]
+pub struct LegacyImportReport {
+    pub imported: usize,
+    /// Paths listed by an exam but missing on disk; left as they are.
+    pub missing: Vec<String>,
+    /// Paths that could not be imported, with the reason.
+    pub failed: Vec<String>,
+}
+
+type ExamRow = (
+    i64,
+    Option<String>,
+    Option<String>,
+    Option<i64>,
+    Option<i64>,
+);
+
+struct Exam {
+    id: i64,
+    consultation_id: Option<i64>,
+    patient_id: Option<i64>,
+}
+
+struct LegacyFile {
+    path: PathBuf,
+    sha256: String,
+    original_name: String,
+    mime_type: &'static str,
+    size: usize,
+}
+
+/// Name given at upload, without the timestamp prefix.
+fn original_name(file_name: &str) -> &str {
+    let stamped = file_name.len() > LEGACY_STAMP_LEN
+        && file_name
+            .bytes()
+            .take(LEGACY_STAMP_LEN)
+            .enumerate()
+            .all(|(i, b)| match i {
+                8 | 15 => b == b'_',
+                _ => b.is_ascii_digit(),
+            });
+    if stamped {
+        &file_name[LEGACY_STAMP_LEN..]
+    } else {
+        file_name
+    }
+}
+
+/// Stores a legacy upload under its checksum; `None` if it is gone.
+fn store_file(app_dir: &Path, key: &Identity, path: &str) -> Result<Option<LegacyFile>, String> {
+    let full_path = safe_path::resolve(app_dir, path)?;
+    if !full_path.is_file() {
+        return Ok(None);
+    }
+    let stored = fs::read(&full_path).map_err(|e| format!("Failed to read file: {}", e))?;
+    // `encrypt_medical_files` may already have encrypted it in place
+    let (data, encrypted) = if crypto::is_encrypted(&stored) {
+        (crypto::decrypt(key, stored.clone(), false)?, stored)
+    } else {
+        let encrypted = crypto::encrypt(key, &stored)?;
+        (stored, encrypted)
+    };
+    let sha256 = store::checksum(&data);
+    store::write_blob(app_dir, &sha256, &encrypted)?;
+
+    let file_name = full_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(Some(LegacyFile {
+        path: full_path,
+        sha256,
+        original_name: original_name(&file_name).chars().take(255).collect(),
+        mime_type: mime::sniff(&data).unwrap_or("application/octet-stream"),
+        size: data.len(),
+    }))
+}
+
+struct Importer<'a> {
+    app_dir: &'a Path,
+    key: &'a Identity,
+    report: LegacyImportReport,
+    /// Imported in the current exam's transaction, deleted once it commits.
+    pending: Vec<PathBuf>,
+    imported: Vec<PathBuf>,
+}
+
+impl Importer<'_> {
+    /// Moves the legacy paths in one JSON column into `medical_files`, linked
+    /// to the exam, and drops them from the list. Paths that could not be
+    /// imported stay. Returns the new column value, or `None` if nothing changed.
+    async fn import_column(
+        &mut self,
+        conn: &mut MySqlConnection,
+        exam: &Exam,
+        kind: &str,
+        json: Option<String>,
+    ) -> Result<Option<String>, String> {
+        let Some(entries) = json
+            .as_deref()
+            .and_then(|json| serde_json::from_str::<Vec<Value>>(json).ok())
+        else {
+            return Ok(None);
+        };
+
+        let mut changed = false;
+        let mut kept = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let Some(path) = entry.as_str().filter(|p| p.starts_with(LEGACY_PREFIX)) else {
+                kept.push(entry);
+                continue;
+            };
+            let path = path.to_string();
+            let file = match store_file(self.app_dir, self.key, &path) {
+                Ok(Some(file)) => file,
+                Ok(None) => {
+                    self.report.missing.push(path);
+                    kept.push(entry);
+                    continue;
+                }
+                Err(e) => {
+                    self.report.failed.push(format!("{}: {}", path, e));
+                    kept.push(entry);
+                    continue;
+                }
+            };
+
+            sqlx::query(
+                "INSERT INTO medical_files (patient_id, consultation_id, exam_id, sha256,
+                                            original_name, kind, mime_type, size_bytes)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(exam.patient_id)
+            .bind(exam.consultation_id)
+            .bind(exam.id)
+            .bind(&file.sha256)
+            .bind(&file.original_name)
+            .bind(kind)
+            .bind(file.mime_type)
+            .bind(file.size as u64)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to record file: {}", e))?;
+
+            self.pending.push(file.path);
+            changed = true;
+        }
+        Ok(changed.then(|| Value::Array(kept).to_string()))
+    }
+}
+
+/// Moves uploads still listed by path in `ecg_ett_exams` into `medical_files`,
+/// linked to their exam, and drops them from the exams' lists. Each exam is
+/// updated in one transaction; the old files are deleted once it commits.
+/// Safe to run again, e.g. after restoring an old full backup.
+#[command]
+pub async fn import_legacy_medical_files(
+    app: AppHandle,
+    db: State<'_, DbConfigState>,
+) -> Result<LegacyImportReport, FileStorageError> {
+    let config = db.current()?;
+    let app_dir = app_data_dir(&app)?;
+    let key = crypto::key(&app).await?;
+    let mut conn = db_config::connect(&config).await?;
+
+    let exams: Vec<ExamRow> = sqlx::query_as(
+        "SELECT CAST(e.id AS SIGNED), e.ecg_files, e.ett_files,
+                CAST(c.id AS SIGNED), CAST(c.patient_db_id AS SIGNED)
+         FROM ecg_ett_exams e
+         LEFT JOIN consultations c ON c.id = e.consultation_id
+         WHERE e.ecg_files LIKE '%medical_files/%' OR e.ett_files LIKE '%medical_files/%'",
+    )
+    .fetch_all(&mut conn)
+    .await
+    .map_err(|e| format!("Failed to list exam files: {}", e))?;
+
+    let mut importer = Importer {
+        app_dir: &app_dir,
+        key,
+        report: LegacyImportReport::default(),
+        pending: Vec::new(),
+        imported: Vec::new(),
+    };
+    for (id, ecg_files, ett_files, consultation_id, patient_id) in exams {
+        let exam = Exam {
+            id,
+            consultation_id,
+            patient_id,
+        };
+        let mut tx = conn
+            .begin()
+            .await
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        let ecg_files = importer
+            .import_column(&mut tx, &exam, "ECG", ecg_files)
+            .await?;
+        let ett_files = importer
+            .import_column(&mut tx, &exam, "ETT", ett_files)
+            .await?;
+        if ecg_files.is_none() && ett_files.is_none() {
+            continue;
+        }
+
+        sqlx::query(
+            "UPDATE ecg_ett_exams
+             SET ecg_files = COALESCE(?, ecg_files), ett_files = COALESCE(?, ett_files)
+             WHERE id = ?",
+        )
+        .bind(ecg_files)
+        .bind(ett_files)
+        .bind(exam.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to update exam {}: {}", exam.id, e))?;
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to update exam {}: {}", exam.id, e))?;
+
+        importer.report.imported += importer.pending.len();
+        importer.imported.append(&mut importer.pending);
+    }
+
+    // Only now that every exam points at the stored copies
+    for path in &importer.imported {
+        let _ = fs::remove_file(path);
+    }
+    Ok(importer.report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_the_upload_timestamp() {
+        assert_eq!(
+            original_name("20240115_093012_ecg_repos.pdf"),
+            "ecg_repos.pdf"
+        );
+        assert_eq!(original_name("ecg_repos.pdf"), "ecg_repos.pdf");
+        assert_eq!(original_name("20240115_093012_"), "20240115_093012_");
+        assert_eq!(
+            original_name("2024011X_093012_a.pdf"),
+            "2024011X_093012_a.pdf"
+        );
+    }
+}
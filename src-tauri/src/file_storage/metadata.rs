@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+use sqlx::Connection;
+use tauri::{command, State};
+
+use super::FileStorageError;
+use crate::db_config::{self, DbConfigState};
+
+/// What a file belongs to. A consultation alone is enough; its patient is
+/// looked up.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FileLinks {
+    pub patient_id: Option<i64>,
+    pub consultation_id: Option<i64>,
+    /// `ecg_ett_exams.id` the file was attached to.
+    pub exam_id: Option<i64>,
+}
+
+/// Falls back to the patient of the linked consultation. Binds the patient
+/// id, then the consultation id.
+pub const PATIENT_ID_SQL: &str =
+    "COALESCE(?, (SELECT patient_db_id FROM consultations WHERE id = ?))";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FileFilter {
+    pub patient_id: Option<i64>,
+    pub consultation_id: Option<i64>,
+    /// `ECG`, `ETT`, ...
+    pub kind: Option<String>,
+    /// Matched anywhere in the original filename.
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MedicalFileInfo {
+    pub id: i64,
+    pub patient_id: Option<i64>,
+    pub consultation_id: Option<i64>,
+    pub exam_id: Option<i64>,
+    pub kind: Option<String>,
+    pub original_name: String,
+    pub mime_type: String,
+    pub size_bytes: i64,
+    /// SHA-256 of the file content.
+    pub checksum: String,
+    pub uploaded_by: Option<i64>,
+    pub created_at: String,
+}
+
+type FileRow = (
+    i64,
+    Option<i64>,
+    Option<i64>,
+    Option<i64>,
+    Option<String>,
+    String,
+    String,
+    i64,
+    String,
+    Option<i64>,
+    String,
+);
+
+/// Files of a patient or consultation, newest first, optionally narrowed by
+/// kind and filename.
+#[command]
+pub async fn list_medical_files(
+    db: State<'_, DbConfigState>,
+    filter: FileFilter,
+) -> Result<Vec<MedicalFileInfo>, FileStorageError> {
+    if filter.patient_id.is_none() && filter.consultation_id.is_none() {
+        return Err("A patient or consultation is required to list files"
+            .to_string()
+            .into());
+    }
+    let name_pattern = filter.query.as_deref().map(|query| {
+        let escaped = query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        format!("%{}%", escaped)
+    });
+
+    let config = db.current()?;
+    let mut conn = db_config::connect(&config).await?;
+    let rows: Vec<FileRow> = sqlx::query_as(
+        "SELECT CAST(id AS SIGNED), CAST(patient_id AS SIGNED), CAST(consultation_id AS SIGNED),
+                CAST(exam_id AS SIGNED), kind, original_name, mime_type, CAST(size_bytes AS SIGNED),
+                sha256, CAST(uploaded_by AS SIGNED), CAST(created_at AS CHAR)
+         FROM medical_files
+         WHERE (? IS NULL OR patient_id = ?)
+           AND (? IS NULL OR consultation_id = ?)
+           AND (? IS NULL OR kind = ?)
+           AND (? IS NULL OR original_name LIKE ?)
+         ORDER BY created_at DESC, id DESC",
+    )
+    .bind(filter.patient_id)
+    .bind(filter.patient_id)
+    .bind(filter.consultation_id)
+    .bind(filter.consultation_id)
+    .bind(&filter.kind)
+    .bind(&filter.kind)
+    .bind(&name_pattern)
+    .bind(&name_pattern)
+    .fetch_all(&mut conn)
+    .await
+    .map_err(|e| format!("Failed to list files: {}", e))?;
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(
+                id,
+                patient_id,
+                consultation_id,
+                exam_id,
+                kind,
+                original_name,
+                mime_type,
+                size_bytes,
+                checksum,
+                uploaded_by,
+                created_at,
+            )| MedicalFileInfo {
+                id,
+                patient_id,
+                consultation_id,
+                exam_id,
+                kind,
+                original_name,
+                mime_type,
+                size_bytes,
+                checksum,
+                uploaded_by,
+                created_at,
+            },
+        )
+        .collect())
+}
+
+/// Attaches files saved before their consultation existed, e.g. uploads made
+/// while the consultation form is still open.
+#[command]
+pub async fn link_medical_files(
+    db: State<'_, DbConfigState>,
+    file_ids: Vec<u64>,
+    links: FileLinks,
+) -> Result<(), FileStorageError> {
+    let config = db.current()?;
+    let mut conn = db_config::connect(&config).await?;
+    let mut tx = conn
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+    for file_id in file_ids {
+        let exists: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM medical_files WHERE id = ?")
+            .bind(file_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to look up file: {}", e))?;
+        if exists == 0 {
+            return Err(FileStorageError::NotFound(file_id));
+        }
+
+        sqlx::query(&format!(
+            "UPDATE medical_files SET patient_id = {}, consultation_id = ?, exam_id = ?
+             WHERE id = ?",
+            PATIENT_ID_SQL
+        ))
+        .bind(links.patient_id)
+        .bind(links.consultation_id)
+        .bind(links.consultation_id)
+        .bind(links.exam_id)
+        .bind(file_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to link file: {}", e))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to link files: {}", e))?;
+    Ok(())
+}
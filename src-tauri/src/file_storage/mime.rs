@@ -0,0 +1,153 @@
+/// Medical files are kept in memory and sent over IPC, so uploads are capped.
+pub const MAX_FILE_SIZE: usize = 50 * 1024 * 1024;
+
+pub const PDF: &str = "application/pdf";
+pub const PNG: &str = "image/png";
+pub const JPEG: &str = "image/jpeg";
+pub const DICOM: &str = "application/dicom";
+/// SCP-ECG (EN 1064) has no registered media type.
+pub const SCP_ECG: &str = "application/x-scp-ecg";
+pub const XML: &str = "application/xml";
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// DICOM Part 10 files have a 128-byte preamble before this marker.
+const DICOM_PREAMBLE: usize = 128;
+/// Section 0 of an SCP-ECG record starts after the record CRC and length.
+const SCP_SECTION_0: usize = 6;
+
+/// Extensions for a type, the usual one first; given to plain copies so the
+/// system picks the right viewer.
+pub fn extensions(mime_type: &str) -> &'static [&'static str] {
+    match mime_type {
+        PDF => &[".pdf"],
+        PNG => &[".png"],
+        JPEG => &[".jpg", ".jpeg"],
+        DICOM => &[".dcm", ".dicom"],
+        SCP_ECG => &[".scp"],
+        XML => &[".xml"],
+        _ => &[],
+    }
+}
+
+/// Detects the type from the content alone; `None` for anything outside the
+/// types the app accepts.
+pub fn sniff(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"%PDF-") {
+        Some(PDF)
+    } else if data.starts_with(PNG_SIGNATURE) {
+        Some(PNG)
+    } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(JPEG)
+    } else if data.get(DICOM_PREAMBLE..DICOM_PREAMBLE + 4) == Some(b"DICM") {
+        Some(DICOM)
+    } else if is_scp_ecg(data) {
+        Some(SCP_ECG)
+    } else if is_xml(data) {
+        Some(XML)
+    } else {
+        None
+    }
+}
+
+/// An SCP-ECG record opens with a CRC over the rest of the file and the
+/// record length, followed by section 0.
+fn is_scp_ecg(data: &[u8]) -> bool {
+    if data.len() < SCP_SECTION_0 + 16 {
+        return false;
+    }
+    let crc = u16::from_le_bytes([data[0], data[1]]);
+    let length = u32::from_le_bytes([data[2], data[3], data[4], data[5]]) as usize;
+    let section_id = u16::from_le_bytes([data[8], data[9]]);
+
+    length == data.len() && section_id == 0 && crc == crc_ccitt(&data[2..])
+}
+
+/// CRC-CCITT as used by SCP-ECG: polynomial 0x1021, initial value 0xFFFF.
+fn crc_ccitt(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, &byte| {
+        (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Only documents with an XML declaration, as exported by ECG carts (HL7
+/// aECG and vendor formats).
+fn is_xml(data: &[u8]) -> bool {
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    let start = data
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(data.len());
+    data[start..].starts_with(b"<?xml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal SCP-ECG record: CRC, length, then a section 0 header.
+    fn scp_ecg() -> Vec<u8> {
+        let mut data = vec![0u8; 64];
+        let length = data.len() as u32;
+        data[2..6].copy_from_slice(&length.to_le_bytes());
+        data[8..10].copy_from_slice(&0u16.to_le_bytes());
+        data[10..14].copy_from_slice(&16u32.to_le_bytes());
+        for (i, byte) in data.iter_mut().enumerate().skip(14) {
+            *byte = i as u8;
+        }
+        let crc = crc_ccitt(&data[2..]);
+        data[..2].copy_from_slice(&crc.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn accepts_documents_and_images() {
+        assert_eq!(sniff(b"%PDF-1.7\n%\xE2\xE3\xCF\xD3"), Some(PDF));
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some(PNG));
+        assert_eq!(sniff(b"\xFF\xD8\xFF\xE0\0\x10JFIF"), Some(JPEG));
+    }
+
+    #[test]
+    fn accepts_dicom_after_the_preamble() {
+        let mut data = vec![0u8; DICOM_PREAMBLE];
+        data.extend_from_slice(b"DICM\x02\x00\x00\x00");
+        assert_eq!(sniff(&data), Some(DICOM));
+        // The marker alone, without the preamble, is not DICOM Part 10
+        assert_eq!(sniff(b"DICM\x02\x00\x00\x00"), None);
+    }
+
+    #[test]
+    fn accepts_scp_ecg_with_a_valid_crc() {
+        assert_eq!(sniff(&scp_ecg()), Some(SCP_ECG));
+    }
+
+    #[test]
+    fn rejects_scp_ecg_with_a_bad_crc() {
+        let mut data = scp_ecg();
+        data[40] ^= 0xFF;
+        assert_eq!(sniff(&data), None);
+    }
+
+    #[test]
+    fn accepts_xml_with_or_without_bom() {
+        assert_eq!(sniff(b"<?xml version=\"1.0\"?><AnnotatedECG/>"), Some(XML));
+        assert_eq!(
+            sniff(b"\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<restingecgdata/>"),
+            Some(XML)
+        );
+        assert_eq!(sniff(b"\r\n  <?xml version=\"1.0\"?><a/>"), Some(XML));
+    }
+
+    #[test]
+    fn rejects_html_and_archives() {
+        assert_eq!(sniff(b"<!DOCTYPE html><html><body></body></html>"), None);
+        assert_eq!(sniff(b"<html><script>alert(1)</script></html>"), None);
+        assert_eq!(sniff(b"PK\x03\x04\x14\x00\x00\x00\x08\x00"), None);
+        assert_eq!(sniff(b""), None);
+    }
+}
@@ -0,0 +1,443 @@
+mod crypto;
+mod error;
+pub mod legacy;
+pub mod metadata;
+mod mime;
+mod store;
+
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::db_config::{self, DbConfig, DbConfigState};
+use crate::safe_path;
+
+pub use crypto::EncryptionReport;
+pub use error::FileStorageError;
+use metadata::FileLinks;
+pub use store::relative_path;
+
+/// Plain copies made for the system viewer, under the app cache directory.
+const OPENED_FILES_DIR: &str = "opened_files";
+const OPENED_FILE_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+fn app_data_dir(app: &AppHandle) -> Result<std::path::PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app directory: {}", e))
+}
+
+/// Stores an upload and returns its id in `medical_files`. Content is keyed
+/// by the SHA-256 of the plain file, so re-uploading the same file reuses the
+/// stored copy; what lands on disk is encrypted with the installation key.
+/// The type is detected from the content and must be one the app accepts.
+#[tauri::command]
+pub async fn save_medical_file(
+    app: AppHandle,
+    db: State<'_, DbConfigState>,
+    file_data: Vec<u8>,
+    filename: String,
+    file_type: String,
+    links: Option<FileLinks>,
+    uploaded_by: Option<i64>,
+) -> Result<u64, FileStorageError> {
+    if file_data.len() > mime::MAX_FILE_SIZE {
+        return Err(FileStorageError::TooLarge {
+            size: file_data.len(),
+            limit: mime::MAX_FILE_SIZE,
+        });
+    }
+    let mime_type = mime::sniff(&file_data)
+        .ok_or_else(|| FileStorageError::UnsupportedType(filename.clone()))?;
+
+    let config = db.current()?;
+    let app_dir = app_data_dir(&app)?;
+    let sha256 = store::checksum(&file_data);
+    let original_name: String = sanitize_filename(&filename).chars().take(255).collect();
+    let encrypted = crypto::encrypt(crypto::key(&app).await?, &file_data)?;
+
+    store::write_blob(&app_dir, &sha256, &encrypted)?;
+
+    let links = links.unwrap_or_default();
+    let mut conn = db_config::connect(&config).await?;
+    let result = sqlx::query(&format!(
+        "INSERT INTO medical_files (patient_id, consultation_id, exam_id, sha256, original_name,
+                                    kind, mime_type, size_bytes, uploaded_by)
+         VALUES ({}, ?, ?, ?, ?, ?, ?, ?, ?)",
+        metadata::PATIENT_ID_SQL
+    ))
+    .bind(links.patient_id)
+    .bind(links.consultation_id)
+    .bind(links.consultation_id)
+    .bind(links.exam_id)
+    .bind(&sha256)
+    .bind(&original_name)
+    .bind(&file_type)
+    .bind(mime_type)
+    .bind(file_data.len() as u64)
+    .bind(uploaded_by)
+    .execute(&mut conn)
+    .await
+    .map_err(|e| format!("Failed to record file: {}", e))?;
+
+    // A concurrent delete of the last reference may have removed the blob
+    store::write_blob(&app_dir, &sha256, &encrypted)?;
+
+    Ok(result.last_insert_id())
+}
+
+async fn file_checksum(
+    conn: &mut sqlx::MySqlConnection,
+    file_id: u64,
+) -> Result<String, FileStorageError> {
+    sqlx::query_scalar("SELECT sha256 FROM medical_files WHERE id = ?")
+        .bind(file_id)
+        .fetch_optional(conn)
+        .await
+        .map_err(|e| format!("Failed to look up file: {}", e))?
+        .ok_or(FileStorageError::NotFound(file_id))
+}
+
+#[derive(Debug, Serialize)]
+pub struct MedicalFile {
+    /// Detected when the file was saved.
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Decrypted content of a stored file, with its original name.
+async fn load_file(
+    app: &AppHandle,
+    db: &State<'_, DbConfigState>,
+    file_id: u64,
+) -> Result<(String, MedicalFile), FileStorageError> {
+    let config = db.current()?;
+    let mut conn = db_config::connect(&config).await?;
+    // Content is shared by checksum, so any row stored before encryption
+    // makes plain content acceptable
+    let (sha256, original_name, mime_type, allow_plain): (String, String, String, bool) =
+        sqlx::query_as(
+            "SELECT f.sha256, f.original_name, f.mime_type,
+                    EXISTS(SELECT 1 FROM medical_files p WHERE p.sha256 = f.sha256 AND NOT p.encrypted)
+             FROM medical_files f WHERE f.id = ?",
+        )
+        .bind(file_id)
+        .fetch_optional(&mut conn)
+        .await
+        .map_err(|e| format!("Failed to look up file: {}", e))?
+        .ok_or(FileStorageError::NotFound(file_id))?;
+
+    let full_path = store::blob_path(&app_data_dir(app)?, &sha256)?;
+    if !full_path.exists() {
+        return Err(FileStorageError::NotFound(file_id));
+    }
+
+    let stored = fs::read(&full_path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let data = crypto::decrypt(crypto::key(app).await?, stored, allow_plain)?;
+    if store::checksum(&data) != sha256 {
+        return Err(format!("File is corrupted: {}", file_id).into());
+    }
+    Ok((original_name, MedicalFile { mime_type, data }))
+}
+
+#[tauri::command]
+pub async fn read_medical_file(
+    app: AppHandle,
+    db: State<'_, DbConfigState>,
+    file_id: u64,
+) -> Result<MedicalFile, FileStorageError> {
+    let (_, file) = load_file(&app, &db, file_id).await?;
+    Ok(file)
+}
+
+/// Drops a file reference; the stored content goes once nothing references it.
+#[tauri::command]
+pub async fn delete_medical_file(
+    app: AppHandle,
+    db: State<'_, DbConfigState>,
+    file_id: u64,
+) -> Result<(), FileStorageError> {
+    let config = db.current()?;
+    let mut conn = db_config::connect(&config).await?;
+    let sha256 = file_checksum(&mut conn, file_id).await?;
+
+    sqlx::query("DELETE FROM medical_files WHERE id = ?")
+        .bind(file_id)
+        .execute(&mut conn)
+        .await
+        .map_err(|e| format!("Failed to delete file: {}", e))?;
+
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM medical_files WHERE sha256 = ?")
+        .bind(&sha256)
+        .fetch_one(&mut conn)
+        .await
+        .map_err(|e| format!("Failed to delete file: {}", e))?;
+    if remaining == 0 {
+        store::remove_blob(&app_data_dir(&app)?, &sha256)?;
+    }
+
+    Ok(())
+}
+
+/// Deletes stored content no `medical_files` row references any more, such
+/// as content left behind by an interrupted upload. Deleting a patient only
+/// unlinks their files, so it never frees content. Returns how many were
+/// removed.
+#[tauri::command]
+pub async fn remove_orphaned_medical_files(
+    app: AppHandle,
+    db: State<'_, DbConfigState>,
+) -> Result<usize, FileStorageError> {
+    let config = db.current()?;
+    Ok(remove_orphaned_blobs(&app, &config).await?)
+}
+
+/// Runs `remove_orphaned_medical_files` in the background once the app is up.
+pub fn start_cleanup(app: &AppHandle) {
+    let Ok(config) = app.state::<DbConfigState>().current() else {
+        return;
+    };
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = remove_orphaned_blobs(&app, &config).await {
+            log::warn!("Failed to remove orphaned medical files: {}", e);
+        }
+    });
+}
+
+async fn remove_orphaned_blobs(app: &AppHandle, config: &DbConfig) -> Result<usize, String> {
+    let app_dir = app_data_dir(app)?;
+    let mut conn = db_config::connect(config).await?;
+    let referenced: HashSet<String> =
+        sqlx::query_scalar("SELECT DISTINCT sha256 FROM medical_files")
+            .fetch_all(&mut conn)
+            .await
+            .map_err(|e| format!("Failed to list medical files: {}", e))?
+            .into_iter()
+            .collect();
+
+    let candidates = {
+        let app_dir = app_dir.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            store::unreferenced_blobs(&app_dir, &referenced)
+        })
+        .await
+        .map_err(|e| format!("Cleanup task failed: {}", e))?
+    };
+    let mut removed = 0;
+    for sha256 in candidates {
+        // Saved again since the listing, e.g. by a patient restore
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM medical_files WHERE sha256 = ?")
+            .bind(&sha256)
+            .fetch_one(&mut conn)
+            .await
+            .map_err(|e| format!("Failed to look up file: {}", e))?;
+        if count == 0 {
+            store::remove_blob(&app_dir, &sha256)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Encrypts medical files written before encryption at rest was enabled,
+/// then records them as encrypted so plain content is refused from then on.
+/// Safe to run again; files already encrypted are left alone.
+#[tauri::command]
+pub async fn encrypt_medical_files(
+    app: AppHandle,
+    db: State<'_, DbConfigState>,
+) -> Result<EncryptionReport, FileStorageError> {
+    let config = db.current()?;
+    let key = crypto::key(&app).await?;
+    let app_dir = app_data_dir(&app)?;
+    let root = app_dir.join(store::MEDICAL_FILES_DIR);
+
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        let mut report = EncryptionReport::default();
+        crypto::encrypt_tree(key, &root, &mut report);
+        report
+    })
+    .await
+    .map_err(|e| format!("Encryption task failed: {}", e))?;
+
+    let mut conn = db_config::connect(&config).await?;
+    let unmarked: Vec<String> =
+        sqlx::query_scalar("SELECT DISTINCT sha256 FROM medical_files WHERE NOT encrypted")
+            .fetch_all(&mut conn)
+            .await
+            .map_err(|e| format!("Failed to list medical files: {}", e))?;
+    for sha256 in unmarked {
+        let encrypted =
+            store::blob_path(&app_dir, &sha256).is_ok_and(|path| crypto::is_encrypted_file(&path));
+        if encrypted {
+            sqlx::query("UPDATE medical_files SET encrypted = TRUE WHERE sha256 = ?")
+                .bind(&sha256)
+                .execute(&mut conn)
+                .await
+                .map_err(|e| format!("Failed to update file: {}", e))?;
+        }
+    }
+    Ok(report)
+}
+
+fn key_file_path(path: String) -> Result<PathBuf, String> {
+    let path = PathBuf::from(path.trim());
+    if !path.is_absolute() {
+        return Err(format!(
+            "Key file path must be absolute: {}",
+            path.display()
+        ));
+    }
+    Ok(path)
+}
+
+/// Writes the medical files key to `path`, encrypted with `passphrase`. With
+/// the passphrase, it is what restoring a full backup on another
+/// installation needs.
+#[tauri::command]
+pub async fn export_medical_files_key(
+    app: AppHandle,
+    path: String,
+    passphrase: String,
+) -> Result<(), FileStorageError> {
+    let path = key_file_path(path)?;
+    let key = crypto::key(&app).await?;
+    // scrypt is deliberately slow
+    tauri::async_runtime::spawn_blocking(move || {
+        let exported = crypto::export_key(key, &passphrase)?;
+        fs::write(&path, exported).map_err(|e| format!("Failed to write key file: {}", e))
+    })
+    .await
+    .map_err(|e| format!("Key export failed: {}", e))??;
+    Ok(())
+}
+
+/// Replaces the medical files key with one written by
+/// `export_medical_files_key`, e.g. before restoring a full backup from
+/// another installation. Refused while files stored here need the current
+/// key, unless `replace` is set.
+#[tauri::command]
+pub async fn import_medical_files_key(
+    app: AppHandle,
+    path: String,
+    passphrase: String,
+    replace: Option<bool>,
+) -> Result<(), FileStorageError> {
+    let path = key_file_path(path)?;
+    let current = crypto::key(&app).await?;
+    let root = app_data_dir(&app)?.join(store::MEDICAL_FILES_DIR);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let exported = fs::read(&path).map_err(|e| format!("Failed to read key file: {}", e))?;
+        let key = crypto::read_exported_key(&exported, &passphrase)?;
+        if crypto::same_key(current, &key) {
+            return Ok(());
+        }
+        if !replace.unwrap_or(false) {
+            let in_use = crypto::count_encrypted_with(current, &root);
+            if in_use > 0 {
+                return Err(format!(
+                    "{} stored files are encrypted with the current key and would become \
+                     unreadable; import with replace to proceed anyway",
+                    in_use
+                ));
+            }
+        }
+        crypto::install_key(&app, key)
+    })
+    .await
+    .map_err(|e| format!("Key import failed: {}", e))??;
+    Ok(())
+}
+
+/// Opens a file in the system viewer. Stored files are encrypted, so a
+/// plain copy is written to the cache folder and handed to the opener
+/// plugin; copies left by earlier opens are cleared out first.
+#[tauri::command]
+pub async fn open_medical_file(
+    app: AppHandle,
+    db: State<'_, DbConfigState>,
+    file_id: u64,
+) -> Result<(), FileStorageError> {
+    let (original_name, file) = load_file(&app, &db, file_id).await?;
+
+    let open_dir = opened_files_dir(&app)?;
+    fs::create_dir_all(&open_dir).map_err(|e| format!("Failed to create directory: {}", e))?;
+    remove_stale_copies(&open_dir);
+
+    let mut name = format!("{}_{}", file_id, sanitize_filename(&original_name));
+    let extensions = mime::extensions(&file.mime_type);
+    let lowercase = name.to_lowercase();
+    if !extensions.iter().any(|ext| lowercase.ends_with(ext)) {
+        if let Some(extension) = extensions.first() {
+            name.push_str(extension);
+        }
+    }
+    let copy_path = safe_path::resolve(&open_dir, &name)?;
+    fs::write(&copy_path, &file.data).map_err(|e| format!("Failed to write file: {}", e))?;
+
+    app.opener()
+        .open_path(copy_path.to_string_lossy(), None::<&str>)
+        .map_err(|e| format!("Failed to open file: {}", e))?;
+    Ok(())
+}
+
+fn opened_files_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| format!("Failed to get app cache directory: {}", e))?
+        .join(OPENED_FILES_DIR))
+}
+
+/// Deletes every plain copy. Run at startup and on exit so none outlives the
+/// session that opened it; copies a viewer still holds (e.g. on Windows) are
+/// left for the next run.
+pub fn clear_opened_copies(app: &AppHandle) {
+    let Ok(open_dir) = opened_files_dir(app) else {
+        return;
+    };
+    let Ok(entries) = fs::read_dir(open_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let _ = fs::remove_file(entry.path());
+    }
+}
+
+/// Deletes plain copies old enough for their viewer to be done with them.
+/// Copies still held open (e.g. on Windows) are retried on the next open.
+fn remove_stale_copies(open_dir: &Path) {
+    let Ok(entries) = fs::read_dir(open_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let stale = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > OPENED_FILE_LIFETIME);
+        if stale {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+fn sanitize_filename(filename: &str) -> String {
+    filename
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,164 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::safe_path;
+
+/// Root of the store, relative to the app data directory.
+pub const MEDICAL_FILES_DIR: &str = "medical_files";
+/// A younger blob may belong to an upload whose row is not written yet.
+const ORPHAN_MIN_AGE: Duration = Duration::from_secs(60 * 60);
+
+pub fn checksum(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// `medical_files/ab/<sha256>`, relative to the app data directory. Sharded on
+/// the first byte so no single folder grows too large. Fails unless `sha256`
+/// is 64 hex digits, as values read back from a database or backup may not be.
+pub fn relative_path(sha256: &str) -> Result<String, String> {
+    if !is_checksum(sha256) {
+        return Err(format!("Invalid file checksum: {}", sha256));
+    }
+    Ok(format!("{}/{}/{}", MEDICAL_FILES_DIR, &sha256[..2], sha256))
+}
+
+pub fn blob_path(app_dir: &Path, sha256: &str) -> Result<PathBuf, String> {
+    safe_path::resolve(app_dir, &relative_path(sha256)?)
+}
+
+/// Stores `data` under its checksum. Identical content is only written once;
+/// a partial write never becomes visible under the final name.
+pub fn write_blob(app_dir: &Path, sha256: &str, data: &[u8]) -> Result<(), String> {
+    let path = blob_path(app_dir, sha256)?;
+    if path.exists() {
+        return Ok(());
+    }
+    let dir = path.parent().unwrap_or(app_dir);
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create medical files directory: {}", e))?;
+
+    let mut staged =
+        tempfile::NamedTempFile::new_in(dir).map_err(|e| format!("Failed to write file: {}", e))?;
+    staged
+        .write_all(data)
+        .and_then(|_| staged.as_file().sync_all())
+        .map_err(|e| format!("Failed to write file: {}", e))?;
+
+    match staged.persist_noclobber(&path) {
+        Ok(_) => Ok(()),
+        // Stored concurrently by another upload of the same content
+        Err(e) if e.error.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+        Err(e) => Err(format!("Failed to write file: {}", e.error)),
+    }
+}
+
+/// Removes a blob and the shard folder if it is left empty.
+pub fn remove_blob(app_dir: &Path, sha256: &str) -> Result<(), String> {
+    let path = blob_path(app_dir, sha256)?;
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| format!("Failed to delete file: {}", e))?;
+    }
+    if let Some(shard) = path.parent() {
+        let _ = fs::remove_dir(shard);
+    }
+    Ok(())
+}
+
+fn is_checksum(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Checksums of the blobs on disk that are not in `referenced` and are old
+/// enough not to be an upload in progress. Legacy uploads stored outside the
+/// shard folders are never listed.
+pub fn unreferenced_blobs(app_dir: &Path, referenced: &HashSet<String>) -> Vec<String> {
+    let Ok(shards) = fs::read_dir(app_dir.join(MEDICAL_FILES_DIR)) else {
+        return Vec::new();
+    };
+    let mut blobs = Vec::new();
+    for shard in shards.flatten() {
+        let shard_name = shard.file_name().to_string_lossy().into_owned();
+        if shard_name.len() != 2 || !shard.path().is_dir() {
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(shard.path()) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !is_checksum(&name) || !name.starts_with(&shard_name) || referenced.contains(&name) {
+                continue;
+            }
+            let old_enough = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > ORPHAN_MIN_AGE);
+            if old_enough {
+                blobs.push(name);
+            }
+        }
+    }
+    blobs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::time::SystemTime;
+
+    fn write(app_dir: &Path, relative: &str, age: Duration) {
+        let path = app_dir.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let file = File::create(&path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    #[test]
+    fn lists_only_old_unreferenced_blobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let day = Duration::from_secs(24 * 60 * 60);
+        let kept = "ab".repeat(32);
+        let orphan = "cd".repeat(32);
+        let uploading = "ef".repeat(32);
+        write(dir.path(), &relative_path(&kept).unwrap(), day);
+        write(dir.path(), &relative_path(&orphan).unwrap(), day);
+        write(
+            dir.path(),
+            &relative_path(&uploading).unwrap(),
+            Duration::ZERO,
+        );
+        write(dir.path(), "medical_files/20240115_093012_ecg.pdf", day);
+        write(dir.path(), "medical_files/ab/.tmpXYZ", day);
+
+        let referenced = HashSet::from([kept]);
+        assert_eq!(unreferenced_blobs(dir.path(), &referenced), vec![orphan]);
+    }
+
+    #[test]
+    fn relative_path_rejects_malformed_checksums() {
+        let sha256 = "0f".repeat(32);
+        assert_eq!(
+            relative_path(&sha256).unwrap(),
+            format!("medical_files/0f/{}", sha256)
+        );
+        for bad in [
+            "",
+            "a",
+            "é",
+            &"0f".repeat(31),
+            &"zz".repeat(32),
+            &"../".repeat(21),
+        ] {
+            assert!(relative_path(bad).is_err(), "{:?}", bad);
+        }
+        // Multi-byte characters must not panic when slicing the shard
+        assert!(relative_path(&format!("é{}", "0".repeat(62))).is_err());
+    }
+}
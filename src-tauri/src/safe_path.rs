@@ -0,0 +1,178 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Names Windows maps to devices in every folder, with or without an extension.
+const RESERVED_NAMES: &[&str] = &["CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$"];
+const RESERVED_PORTS: &[&str] = &["COM", "LPT"];
+
+/// Whether Windows would open a device instead of a file for `segment`,
+/// e.g. `NUL`, `com1.txt` or `Aux .pdf`.
+fn is_device_name(segment: &str) -> bool {
+    let stem = segment.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|name| stem.eq_ignore_ascii_case(name))
+    {
+        return true;
+    }
+    let mut chars = stem.chars();
+    let port: String = chars.by_ref().take(3).collect();
+    let number: Vec<char> = chars.collect();
+    RESERVED_PORTS
+        .iter()
+        .any(|name| port.eq_ignore_ascii_case(name))
+        && matches!(number[..], ['0'..='9' | '¹' | '²' | '³'])
+}
+
+/// Resolves a user-supplied relative path under `root`, rejecting anything
+/// that could escape it: `..` segments, absolute paths, Windows drive or UNC
+/// prefixes, Windows device names, and symlinks pointing outside the root.
+///
+/// Both `/` and `\` are treated as separators so the same input is judged
+/// identically on every platform. The target does not need to exist.
+pub fn resolve(root: &Path, relative: &str) -> Result<PathBuf, String> {
+    let invalid = || format!("Invalid path: {}", relative);
+
+    if relative.trim().is_empty() || relative.contains('\0') {
+        return Err(invalid());
+    }
+
+    let bytes = relative.as_bytes();
+    let has_drive_prefix = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    if has_drive_prefix || relative.starts_with('/') || relative.starts_with('\\') {
+        return Err(invalid());
+    }
+
+    let mut candidate = root.to_path_buf();
+    for segment in relative.split(['/', '\\']) {
+        match segment {
+            "" | "." => continue,
+            ".." => return Err(invalid()),
+            // Alternate data streams and device names are only meaningful on Windows
+            s if s.contains(':') || is_device_name(s) => return Err(invalid()),
+            s => candidate.push(s),
+        }
+    }
+
+    if candidate == root {
+        return Err(invalid());
+    }
+
+    // Symlinks: compare the real location of the deepest existing ancestor.
+    // A dangling link counts as existing, so its target gets checked too.
+    let canonical_root = root
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", root.display(), e))?;
+    let mut existing = candidate.as_path();
+    while fs::symlink_metadata(existing).is_err() {
+        existing = existing.parent().ok_or_else(invalid)?;
+    }
+    let canonical = existing
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", relative, e))?;
+    if !canonical.starts_with(&canonical_root) {
+        return Err(invalid());
+    }
+
+    Ok(candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("medical_files")).unwrap();
+        fs::write(dir.path().join("medical_files/ecg.pdf"), b"%PDF").unwrap();
+        dir
+    }
+
+    #[test]
+    fn accepts_paths_inside_root() {
+        let dir = root();
+        let resolved = resolve(dir.path(), "medical_files/ecg.pdf").unwrap();
+        assert_eq!(resolved, dir.path().join("medical_files").join("ecg.pdf"));
+        assert!(resolve(dir.path(), "medical_files/new.pdf").is_ok());
+        assert!(resolve(dir.path(), "./medical_files\\ecg.pdf").is_ok());
+    }
+
+    #[test]
+    fn rejects_parent_segments() {
+        let dir = root();
+        assert!(resolve(dir.path(), "../secret.sql").is_err());
+        assert!(resolve(dir.path(), "medical_files/../../etc/passwd").is_err());
+        assert!(resolve(dir.path(), "medical_files\\..\\..\\boot.ini").is_err());
+    }
+
+    #[test]
+    fn rejects_absolute_paths() {
+        let dir = root();
+        assert!(resolve(dir.path(), "/etc/passwd").is_err());
+        assert!(resolve(dir.path(), "\\Windows\\win.ini").is_err());
+        assert!(resolve(dir.path(), "\\\\server\\share\\file").is_err());
+    }
+
+    #[test]
+    fn rejects_windows_drive_prefixes() {
+        let dir = root();
+        assert!(resolve(dir.path(), "C:\\Windows\\win.ini").is_err());
+        assert!(resolve(dir.path(), "c:relative.txt").is_err());
+        assert!(resolve(dir.path(), "medical_files/ecg.pdf:stream").is_err());
+    }
+
+    #[test]
+    fn rejects_windows_device_names() {
+        let dir = root();
+        for name in [
+            "CON",
+            "nul",
+            "medical_files/aux.pdf",
+            "medical_files/Com1",
+            "medical_files/lpt9.tar.gz",
+            "medical_files/COM¹.txt",
+            "medical_files/PRN .txt",
+            "conin$",
+        ] {
+            assert!(resolve(dir.path(), name).is_err(), "{}", name);
+        }
+        for name in [
+            "medical_files/console.pdf",
+            "medical_files/com10",
+            "medical_files/lpt.pdf",
+            "medical_files/null.txt",
+            "medical_files/ecg.con",
+        ] {
+            assert!(resolve(dir.path(), name).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_empty_and_root_itself() {
+        let dir = root();
+        assert!(resolve(dir.path(), "").is_err());
+        assert!(resolve(dir.path(), ".").is_err());
+        assert!(resolve(dir.path(), "./").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_escaping_root() {
+        let dir = root();
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("secret.txt"), b"secret").unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("medical_files/link")).unwrap();
+
+        assert!(resolve(dir.path(), "medical_files/link/secret.txt").is_err());
+        assert!(resolve(dir.path(), "medical_files/link/missing.txt").is_err());
+
+        // Dangling: writing through it would create the file outside the root
+        std::os::unix::fs::symlink(
+            outside.path().join("new.pdf"),
+            dir.path().join("medical_files/dangling.pdf"),
+        )
+        .unwrap();
+        assert!(resolve(dir.path(), "medical_files/dangling.pdf").is_err());
+        assert!(resolve(dir.path(), "medical_files/dangling.pdf/inner").is_err());
+    }
+}
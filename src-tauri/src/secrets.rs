@@ -0,0 +1,35 @@
+/// Service name for every credential the app keeps in the OS keyring.
+pub const KEYRING_SERVICE: &str = "CardioPc";
+
+fn entry(account: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, account)
+        .map_err(|e| format!("System keyring unavailable: {}", e))
+}
+
+/// Blocking; call from a blocking thread or a synchronous command.
+pub fn get(account: &str) -> Result<Option<String>, String> {
+    match entry(account)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!(
+            "Failed to read {} from the system keyring: {}",
+            account, e
+        )),
+    }
+}
+
+pub fn set(account: &str, secret: &str) -> Result<(), String> {
+    entry(account)?
+        .set_password(secret)
+        .map_err(|e| format!("Failed to store {} in the system keyring: {}", account, e))
+}
+
+pub fn delete(account: &str) -> Result<(), String> {
+    match entry(account)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!(
+            "Failed to remove {} from the system keyring: {}",
+            account, e
+        )),
+    }
+}
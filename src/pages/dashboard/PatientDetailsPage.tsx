@@ -4,6 +4,7 @@ import { Button } from '../../components/ui/Button';
 import { patientService, consultationService, systemService, fileService } from '../../services/api';
 import { generatePatientDetailsPDF } from '../../services/patientDetailsPDF';
 import { generatePrescriptionPDF, PrescriptionGroup } from '../../services/prescriptionPDF';
+import { FileStorageService, MedicalFileInfo } from '../../services/fileStorage';
 
 type Tab = 'overview' | 'consultations' | 'exams' | 'prescriptions';
 
@@ -34,6 +35,19 @@ export const PatientDetailsPage: React.FC = () => {
             const cData = await consultationService.getConsultations(dbId);
             setConsultations(cData || []);
 
+            // Exam attachments are linked through medical_files.exam_id
+            const patientFiles = await FileStorageService.listFiles({ patient_id: dbId }).catch((err) => {
+                console.error('Failed to load patient files:', err);
+                return [] as MedicalFileInfo[];
+            });
+            const examFile = (examId: number, kind: 'ECG' | 'ETT', legacy: unknown) => {
+                const linked = patientFiles.find(f => f.exam_id === examId && f.kind === kind);
+                if (linked) return { id: linked.id, name: linked.original_name };
+                // Paths not yet moved by import_legacy_medical_files
+                const path = Array.isArray(legacy) ? legacy.find((p: unknown) => typeof p === 'string') : null;
+                return path ? { path, name: path.split('/').pop() || path } : null;
+            };
+
             const examCards: any[] = [];
             cData?.forEach((c: any) => {
                 if (c.ecg_ett_exam) {
@@ -44,7 +58,7 @@ export const PatientDetailsPage: React.FC = () => {
                             date: new Date(c.created_at).toLocaleDateString(),
                             type: 'ECG',
                             result: e.ecg_interpretation,
-                            file: e.ecg_files && e.ecg_files.length > 0 ? e.ecg_files[0] : null
+                            file: examFile(e.id, 'ECG', e.ecg_files)
                         });
                     }
                     if (e.ett_interpretation || e.ett_fevg) {
@@ -53,7 +67,7 @@ export const PatientDetailsPage: React.FC = () => {
                             date: new Date(c.created_at).toLocaleDateString(),
                             type: 'ETT (Echo cardiaque)',
                             result: `FEVG: ${e.ett_fevg || 'N/A'}%, ${e.ett_interpretation || ''}`,
-                            file: e.ett_files && e.ett_files.length > 0 ? e.ett_files[0] : null
+                            file: examFile(e.id, 'ETT', e.ett_files)
                         });
                     }
                 }
@@ -108,9 +122,16 @@ export const PatientDetailsPage: React.FC = () => {
         if (patient) generatePrescriptionPDF(prescription, patient.full_name);
     };
 
-    const handleDownloadFile = (fileName: string) => {
-        if (!fileName) return;
-        fileService.downloadFile(fileName);
+    const handleDownloadFile = async (file: { id?: number; path?: string }) => {
+        try {
+            if (file.id !== undefined) {
+                await FileStorageService.openFile(file.id);
+            } else if (file.path) {
+                fileService.downloadFile(file.path);
+            }
+        } catch (err: any) {
+            alert(err?.message ?? err);
+        }
     };
 
     const [isExamModalOpen, setIsExamModalOpen] = useState(false);
@@ -337,7 +358,7 @@ export const PatientDetailsPage: React.FC = () => {
                                             <div className="flex items-center justify-between pt-4 border-t border-gray-50">
                                                 <div className="flex items-center gap-2 text-emerald-600">
                                                     <span className="material-symbols-outlined text-sm">attachment</span>
-                                                    <span className="text-[10px] font-bold truncate max-w-[150px]">{exam.file}</span>
+                                                    <span className="text-[10px] font-bold truncate max-w-[150px]">{exam.file.name}</span>
                                                 </div>
                                                 <button 
                                                     onClick={() => handleDownloadFile(exam.file)}
@@ -2,6 +2,19 @@ import React, { useState, useEffect } from 'react';
 import { securityService } from '../../../services/api';
 import { invoke } from '@tauri-apps/api/core';
 
+interface RestorePreview {
+    preview_id: string;
+    filename: string;
+    live_migration_version: number | null;
+    backup_migration_version: number | null;
+    tables: {
+        table: string;
+        live_rows: number | null;
+        backup_rows: number | null;
+        difference: number;
+    }[];
+}
+
 export const SecurityBackupsTab: React.FC = () => {
     const [subTab, setSubTab] = useState<'history' | 'audit'>('history');
     const [backups, setBackups] = useState<any[]>([]);
@@ -66,17 +79,49 @@ export const SecurityBackupsTab: React.FC = () => {
     };
 
     const handleRestoreBackup = async (filename: string) => {
-        if (!confirm(`Are you sure you want to restore the database from "${filename}"? This will overwrite all current data!`)) {
+        let preview: RestorePreview;
+        try {
+            // Loads the backup into a scratch schema; the live database is untouched
+            preview = await invoke<RestorePreview>('preview_database_restore', { filename });
+        } catch (error: any) {
+            console.error('Restore preview failed:', error);
+            alert(`Restore preview failed: ${error?.message ?? error}`);
+            return;
+        }
+
+        const changes = preview.tables
+            .filter(t => t.difference !== 0)
+            .map(t => `  ${t.table}: ${t.live_rows ?? '-'} → ${t.backup_rows ?? '-'}`);
+        const summary = changes.length > 0
+            ? `Row counts that will change:\n${changes.join('\n')}`
+            : 'Row counts are the same as the current database.';
+        if (!confirm(`Restore the database from "${filename}"?\n\n${summary}\n\nThis will overwrite all current data!`)) {
+            await invoke('cancel_database_restore').catch(() => undefined);
             return;
         }
 
+        const confirmRestore = (allowOlderSchema: boolean) =>
+            invoke<string>('confirm_database_restore', { previewId: preview.preview_id, allowOlderSchema });
+
         try {
-            const result = await invoke<string>('restore_database_backup', { filename });
+            let result: string;
+            try {
+                result = await confirmRestore(false);
+            } catch (error: any) {
+                if (error?.code !== 'older_schema') {
+                    throw error;
+                }
+                if (!confirm(`${error.message}.\n\nRestore it anyway?`)) {
+                    await invoke('cancel_database_restore').catch(() => undefined);
+                    return;
+                }
+                result = await confirmRestore(true);
+            }
             alert(result);
             window.location.reload(); // Reload app after restore
         } catch (error: any) {
             console.error('Restore failed:', error);
-            alert(`Restore failed: ${error}`);
+            alert(`Restore failed: ${error?.message ?? error}`);
         }
     };
 
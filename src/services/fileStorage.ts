@@ -1,80 +1,191 @@
 import { invoke } from '@tauri-apps/api/core';
 
+export interface MedicalFileLinks {
+    patient_id?: number;
+    consultation_id?: number;
+    exam_id?: number;
+}
+
+export interface MedicalFileInfo {
+    id: number;
+    patient_id: number | null;
+    consultation_id: number | null;
+    exam_id: number | null;
+    kind: string | null;
+    original_name: string;
+    mime_type: string;
+    size_bytes: number;
+    checksum: string;
+    uploaded_by: number | null;
+    created_at: string;
+}
+
 export class FileStorageService {
-    static async saveFile(file: File, fileType: 'ECG' | 'ETT'): Promise<string> {
+    static async saveFile(
+        file: File,
+        fileType: 'ECG' | 'ETT',
+        links?: MedicalFileLinks,
+        uploadedBy?: number
+    ): Promise<number> {
         try {
             const buffer = await file.arrayBuffer();
             const uint8Array = new Uint8Array(buffer);
             const dataArray = Array.from(uint8Array);
 
-            const filePath = await invoke<string>('save_medical_file', {
+            const fileId = await invoke<number>('save_medical_file', {
                 fileData: dataArray,
                 filename: file.name,
-                fileType: fileType
+                fileType: fileType,
+                links: links ?? null,
+                uploadedBy: uploadedBy ?? null
             });
 
-            return filePath;
+            return fileId;
         } catch (error) {
             console.error('Error saving file:', error);
-            const errorMsg = error instanceof Error ? error.message : String(error);
+            const errorMsg = (error as { message?: string })?.message ?? String(error);
             throw new Error(`Échec de la sauvegarde du fichier ${file.name}: ${errorMsg}`);
         }
     }
 
-    static async saveFiles(files: File[], fileType: 'ECG' | 'ETT'): Promise<string[]> {
-        const filePaths: string[] = [];
+    static async saveFiles(
+        files: File[],
+        fileType: 'ECG' | 'ETT',
+        links?: MedicalFileLinks,
+        uploadedBy?: number
+    ): Promise<number[]> {
+        const fileIds: number[] = [];
         const errors: string[] = [];
         
         for (const file of files) {
             try {
-                const path = await this.saveFile(file, fileType);
-                filePaths.push(path);
+                const fileId = await this.saveFile(file, fileType, links, uploadedBy);
+                fileIds.push(fileId);
             } catch (error) {
-                const errorMsg = error instanceof Error ? error.message : String(error);
+                const errorMsg = (error as { message?: string })?.message ?? String(error);
                 console.error(`Failed to save ${file.name}:`, errorMsg);
                 errors.push(`${file.name}: ${errorMsg}`);
             }
         }
         
-        if (errors.length > 0 && filePaths.length === 0) {
+        if (errors.length > 0 && fileIds.length === 0) {
             throw new Error(`Tous les fichiers ont échoué:\n${errors.join('\n')}`);
         }
         
-        return filePaths;
+        return fileIds;
     }
 
-    static async readFile(filePath: string): Promise<Uint8Array> {
+    static async readFile(fileId: number): Promise<{ mimeType: string; data: Uint8Array }> {
         try {
-            const dataArray = await invoke<number[]>('read_medical_file', {
-                filePath: filePath
+            const file = await invoke<{ mime_type: string; data: number[] }>('read_medical_file', {
+                fileId: fileId
             });
 
-            return new Uint8Array(dataArray);
+            return { mimeType: file.mime_type, data: new Uint8Array(file.data) };
         } catch (error) {
             console.error('Error reading file:', error);
-            const errorMsg = error instanceof Error ? error.message : String(error);
+            const errorMsg = (error as { message?: string })?.message ?? String(error);
             throw new Error(`Échec de la lecture du fichier: ${errorMsg}`);
         }
     }
 
-    static async deleteFile(filePath: string): Promise<void> {
+    static async openFile(fileId: number): Promise<void> {
+        try {
+            await invoke('open_medical_file', { fileId });
+        } catch (error) {
+            console.error('Error opening file:', error);
+            const errorMsg = (error as { message?: string })?.message ?? String(error);
+            throw new Error(`Échec de l'ouverture du fichier: ${errorMsg}`);
+        }
+    }
+
+    static async deleteFile(fileId: number): Promise<void> {
         try {
             await invoke('delete_medical_file', {
-                filePath: filePath
+                fileId: fileId
             });
         } catch (error) {
             console.error('Error deleting file:', error);
-            const errorMsg = error instanceof Error ? error.message : String(error);
+            const errorMsg = (error as { message?: string })?.message ?? String(error);
             throw new Error(`Échec de la suppression du fichier: ${errorMsg}`);
         }
     }
 
-    static async deleteFiles(filePaths: string[]): Promise<void> {
-        for (const path of filePaths) {
+    static async listFiles(filter: {
+        patient_id?: number;
+        consultation_id?: number;
+        kind?: string;
+        query?: string;
+    }): Promise<MedicalFileInfo[]> {
+        try {
+            return await invoke<MedicalFileInfo[]>('list_medical_files', { filter });
+        } catch (error) {
+            console.error('Error listing files:', error);
+            const errorMsg = (error as { message?: string })?.message ?? String(error);
+            throw new Error(`Échec du chargement des fichiers: ${errorMsg}`);
+        }
+    }
+
+    /** Encrypts files stored before encryption at rest; safe to run again. */
+    static async encryptExistingFiles(): Promise<{ encrypted: number; already_encrypted: number; failed: string[] }> {
+        try {
+            return await invoke('encrypt_medical_files');
+        } catch (error) {
+            console.error('Error encrypting files:', error);
+            const errorMsg = (error as { message?: string })?.message ?? String(error);
+            throw new Error(`Échec du chiffrement des fichiers: ${errorMsg}`);
+        }
+    }
+
+    /** Saves the files key, protected by a passphrase; needed to restore full backups elsewhere. */
+    static async exportKey(path: string, passphrase: string): Promise<void> {
+        try {
+            await invoke('export_medical_files_key', { path, passphrase });
+        } catch (error) {
+            console.error('Error exporting key:', error);
+            const errorMsg = (error as { message?: string })?.message ?? String(error);
+            throw new Error(`Échec de l'export de la clé: ${errorMsg}`);
+        }
+    }
+
+    static async importKey(path: string, passphrase: string, replace = false): Promise<void> {
+        try {
+            await invoke('import_medical_files_key', { path, passphrase, replace });
+        } catch (error) {
+            console.error('Error importing key:', error);
+            const errorMsg = (error as { message?: string })?.message ?? String(error);
+            throw new Error(`Échec de l'import de la clé: ${errorMsg}`);
+        }
+    }
+
+    /** Moves uploads still referenced by path into the file store; safe to run again. */
+    static async importLegacyFiles(): Promise<{ imported: number; missing: string[]; failed: string[] }> {
+        try {
+            return await invoke('import_legacy_medical_files');
+        } catch (error) {
+            console.error('Error importing legacy files:', error);
+            const errorMsg = (error as { message?: string })?.message ?? String(error);
+            throw new Error(`Échec de l'import des anciens fichiers: ${errorMsg}`);
+        }
+    }
+
+    /** Deletes stored content no file references any more; returns how many were removed. */
+    static async removeOrphanedFiles(): Promise<number> {
+        try {
+            return await invoke<number>('remove_orphaned_medical_files');
+        } catch (error) {
+            console.error('Error removing orphaned files:', error);
+            const errorMsg = (error as { message?: string })?.message ?? String(error);
+            throw new Error(`Échec du nettoyage des fichiers orphelins: ${errorMsg}`);
+        }
+    }
+
+    static async deleteFiles(fileIds: number[]): Promise<void> {
+        for (const fileId of fileIds) {
             try {
-                await this.deleteFile(path);
+                await this.deleteFile(fileId);
             } catch (error) {
-                console.error(`Failed to delete ${path}:`, error);
+                console.error(`Failed to delete file ${fileId}:`, error);
             }
         }
     }